@@ -1,13 +1,13 @@
 from main_window import *
 from PyQt5 import QtCore
-from PyQt5.QtCore import Qt
+from PyQt5.QtCore import Qt, QTimer
 from PyQt5.QtWidgets import QApplication, QMainWindow
 from PyQt5.QtGui import QPixmap
 
 import sys
 from enum import Enum, auto
 
-from mandelbrot_module import PlotWindow
+from mandelbrot_module import PlotWindow, RenderCancelled
 
 
 class Modes(Enum):
@@ -15,6 +15,17 @@ class Modes(Enum):
     Julia = auto()
 
 
+def buffer_pixmap(buffer, width, height):
+    image = QtGui.QImage(buffer, width, height, 3 * width, QtGui.QImage.Format_RGB888)
+    return QPixmap.fromImage(image)
+
+
+def pixmap(frame):
+    # frames are rgb shaped (height, width, 3), rows from top to bottom
+    height, width, _ = frame.shape
+    return buffer_pixmap(bytes(frame), width, height)
+
+
 class MainWindow(QMainWindow):
     def __init__(self):
         super().__init__()
@@ -40,11 +51,14 @@ class MainWindow(QMainWindow):
 
         # maps pixels to 2D coordinates
         self.plot_window = self.get_new_plot_window()
+
+        # renders running in the background, one per scene. The timer shows their passes and results
+        self.renders = {Modes.Mandelbrot: None, Modes.Julia: None}
+        self.timer = QTimer(self)
+        self.timer.timeout.connect(self.poll_renders)
         
         # render first image
         self.mode = Modes.Mandelbrot
-        self.mandel_path = None
-        self.julia_path = None
         self.load_image()
 
     def get_new_plot_window(self):
@@ -61,39 +75,68 @@ class MainWindow(QMainWindow):
             self.load_julia()
 
     def load_mandelbrot(self):
-        if not self.mandel_valid:
+        if not self.mandel_valid and self.renders[Modes.Mandelbrot] is None:
             # generate mandel_scene
-            self.mandel_path = self.plot_window.load_mandelbrot(self.tries, self.power)
-            pixmap = QPixmap(self.mandel_path)
-
-            self.mandel_item = QtWidgets.QGraphicsPixmapItem(pixmap)
+            self.mandel_item = QtWidgets.QGraphicsPixmapItem()
             self.mandel_item.setTransformationMode(Qt.SmoothTransformation)
             
             self.mandel_scene = ResponsiveScene(self)
             self.mandel_scene.addItem(self.mandel_item)
+            self.set_scene(self.mandel_scene)
 
-            self.mandel_valid = True
+            self.start_render(Modes.Mandelbrot, self.mandel_item, self.plot_window.start_mandelbrot)
         # render mandel_scene
         self.set_scene(self.mandel_scene)
         self.resize_image()
 
     def load_julia(self):
-        if not self.julia_valid:
+        if not self.julia_valid and self.renders[Modes.Julia] is None:
             # generate julia_scene
-            self.julia_path = self.plot_window.load_julia(self.tries, self.power)
-            pixmap = QPixmap(self.julia_path)
-
-            self.julia_item = QtWidgets.QGraphicsPixmapItem(pixmap)
+            self.julia_item = QtWidgets.QGraphicsPixmapItem()
             self.julia_item.setTransformationMode(Qt.SmoothTransformation)
             
             self.julia_scene = ResponsiveScene(self)
             self.julia_scene.addItem(self.julia_item)
+            self.set_scene(self.julia_scene)
 
-            self.julia_valid = True
+            self.start_render(Modes.Julia, self.julia_item, self.plot_window.start_julia)
         # render julia_scene
         self.set_scene(self.julia_scene)
         self.resize_image()
 
+    def start_render(self, mode, item, start):
+        # the render thread only keeps the latest pass, widgets can only be touched from the gui thread
+        passes = []
+        def preview(buffer, width, height):
+            passes[:] = [(buffer, width, height)]
+        self.renders[mode] = (start(self.tries, self.power, preview), item, passes)
+        self.timer.start(50)
+
+    def poll_renders(self):
+        for mode, render in self.renders.items():
+            if render is not None:
+                self.poll_render(mode, *render)
+        if all(render is None for render in self.renders.values()):
+            self.timer.stop()
+
+    def poll_render(self, mode, handle, item, passes):
+        if passes:
+            item.setPixmap(buffer_pixmap(*passes.pop()))
+            self.resize_image()
+        if not handle.is_done():
+            return
+        self.renders[mode] = None
+        try:
+            item.setPixmap(pixmap(handle.result()))
+        except RenderCancelled:
+            return
+        # only a finished render makes the scene valid, anything else gets rendered again next time
+        if mode == Modes.Mandelbrot:
+            self.mandel_valid = True
+        elif mode == Modes.Julia:
+            self.julia_valid = True
+        self.resize_image()
+
     def set_scene(self, scene):
         self.ui.graphicsView.setScene(scene)
 
@@ -109,13 +152,20 @@ class MainWindow(QMainWindow):
         self.load_image()
 
     def invalidate_all(self):
-        self.mandel_valid = False
-        self.julia_valid = False
+        self.invalidate_scene(Modes.Mandelbrot)
+        self.invalidate_scene(Modes.Julia)
 
     def invalidate(self):
-        if self.mode == Modes.Mandelbrot:
+        self.invalidate_scene(self.mode)
+
+    def invalidate_scene(self, mode):
+        # the render of the old view is of no use anymore
+        if self.renders[mode] is not None:
+            self.renders[mode][0].cancel()
+            self.renders[mode] = None
+        if mode == Modes.Mandelbrot:
             self.mandel_valid = False
-        elif self.mode == Modes.Julia:
+        elif mode == Modes.Julia:
             self.julia_valid = False
 
 
@@ -168,7 +218,7 @@ class ResponsiveScene(QtWidgets.QGraphicsScene):
         y = point.y()
         print("Scene", x, y)
         cords = (x,y)
-        self.window.julia_valid = False
+        self.window.invalidate_scene(Modes.Julia)
         self.window.plot_window.set_julia(cords)
         self.window.change_mode()
         print("Mode", self.window.mode)
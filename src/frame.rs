@@ -0,0 +1,234 @@
+use pyo3::prelude::*;
+use pyo3::class::PyBufferProtocol;
+use pyo3::exceptions::PyBufferError;
+use pyo3::types::PyBytes;
+use pyo3::{ffi, AsPyPointer};
+use std::os::raw::{c_int, c_void};
+use std::ptr;
+
+use crate::julia::complex::Complex;
+
+enum Data {
+    Rgb(Vec<u8>),
+    Counts(Vec<u32>),
+    Values(Vec<f64>),
+}
+
+impl Data {
+    fn as_bytes(&self) -> (*const c_void, usize) {
+        match self {
+            Data::Rgb(v) => (v.as_ptr() as *const c_void, v.len()),
+            Data::Counts(v) => (v.as_ptr() as *const c_void, v.len() * 4),
+            Data::Values(v) => (v.as_ptr() as *const c_void, v.len() * 8),
+        }
+    }
+
+    // struct module format and size of one item
+    fn format(&self) -> (&'static [u8], isize) {
+        match self {
+            Data::Rgb(_) => (b"B\0", 1),
+            Data::Counts(_) => (b"I\0", 4),
+            Data::Values(_) => (b"d\0", 8),
+        }
+    }
+}
+
+/// Render result handed to python without copying, through the buffer protocol.
+/// Colors are shaped (height, width, 3), iteration fields (height, width).
+#[pyclass]
+pub struct Frame {
+    data: Data,
+    shape: Vec<isize>,
+    strides: Vec<isize>,
+}
+
+impl Frame {
+    fn new(data: Data, shape: Vec<isize>) -> Self {
+        let (_, itemsize) = data.format();
+        // c order: the last axis is contiguous
+        let mut strides = vec![itemsize; shape.len()];
+        for i in (0..shape.len() - 1).rev() {
+            strides[i] = strides[i + 1] * shape[i + 1];
+        }
+        Frame { data, shape, strides }
+    }
+
+    // rgb rows from top to bottom, like ImageBuffer::into_raw
+    pub fn rgb(width: u32, height: u32, pixels: Vec<u8>) -> Self {
+        Self::new(Data::Rgb(pixels), vec![height as isize, width as isize, 3])
+    }
+
+    // iteration counts, rows from top to bottom
+    pub fn counts(width: u32, height: u32, counts: Vec<u32>) -> Self {
+        Self::new(Data::Counts(counts), vec![height as isize, width as isize])
+    }
+
+    // one float per pixel, like the smooth iteration count
+    pub fn values(width: u32, height: u32, values: Vec<f64>) -> Self {
+        Self::new(Data::Values(values), vec![height as isize, width as isize])
+    }
+
+    // real and imaginary part per pixel, shaped (height, width, 2)
+    pub fn complex(width: u32, height: u32, values: Vec<Complex>) -> Self {
+        let parts = values.into_iter().flat_map(|z| [z.real(), z.imag()]).collect();
+        Self::new(Data::Values(parts), vec![height as isize, width as isize, 2])
+    }
+
+    // the frame itself, or a numpy array viewing it
+    pub fn into_py_result(self, py: Python, numpy: bool) -> PyResult<PyObject> {
+        let frame = Py::new(py, self)?;
+        if numpy {
+            Ok(py.import("numpy")?.call_method1("asarray", (frame,))?.into())
+        } else {
+            Ok(frame.into_py(py))
+        }
+    }
+}
+
+#[pymethods]
+impl Frame {
+    #[getter]
+    fn shape(&self) -> Vec<isize> {
+        self.shape.clone()
+    }
+
+    fn tobytes<'p>(&self, py: Python<'p>) -> &'p PyBytes {
+        let (buf, len) = self.data.as_bytes();
+        PyBytes::new(py, unsafe { std::slice::from_raw_parts(buf as *const u8, len) })
+    }
+}
+
+// what a buffer request with the given flags gets to see
+struct Layout {
+    buf: *const c_void,
+    len: isize,
+    format: Option<&'static [u8]>,
+    itemsize: isize,
+    // None for plain contiguous bytes
+    shape: Option<(*const isize, c_int)>,
+    strides: Option<*const isize>,
+}
+
+impl Frame {
+    fn layout(&self, flags: c_int) -> Result<Layout, &'static str> {
+        if (flags & ffi::PyBUF_WRITABLE) == ffi::PyBUF_WRITABLE {
+            return Err("frames are read only");
+        }
+        let (buf, len) = self.data.as_bytes();
+        let (format, itemsize) = self.data.format();
+        let wants_format = (flags & ffi::PyBUF_FORMAT) == ffi::PyBUF_FORMAT;
+        let wants_shape = (flags & ffi::PyBUF_ND) == ffi::PyBUF_ND;
+        if wants_shape && !wants_format && itemsize != 1 {
+            return Err("frame items aren't bytes, the format has to be requested");
+        }
+        let wants_strides = (flags & ffi::PyBUF_STRIDES) == ffi::PyBUF_STRIDES;
+        Ok(Layout {
+            buf,
+            len: len as isize,
+            format: if wants_format { Some(format) } else { None },
+            // plain bytes unless the items are described
+            itemsize: if wants_shape || wants_format { itemsize } else { 1 },
+            shape: if wants_shape { Some((self.shape.as_ptr(), self.shape.len() as c_int)) } else { None },
+            strides: if wants_shape && wants_strides { Some(self.strides.as_ptr()) } else { None },
+        })
+    }
+}
+
+#[pyproto]
+impl PyBufferProtocol for Frame {
+    fn bf_getbuffer(slf: PyRefMut<Self>, view: *mut ffi::Py_buffer, flags: c_int) -> PyResult<()> {
+        if view.is_null() {
+            return Err(PyBufferError::new_err("view is null"));
+        }
+        let layout = slf.layout(flags).map_err(PyBufferError::new_err)?;
+
+        unsafe {
+            (*view).obj = ffi::_Py_NewRef(slf.as_ptr());
+            (*view).buf = layout.buf as *mut c_void;
+            (*view).len = layout.len;
+            (*view).readonly = 1;
+            (*view).suboffsets = ptr::null_mut();
+            (*view).internal = ptr::null_mut();
+            (*view).format = layout.format.map_or(ptr::null_mut(), |format| format.as_ptr() as *mut _);
+            (*view).itemsize = layout.itemsize;
+            match layout.shape {
+                Some((shape, ndim)) => {
+                    (*view).ndim = ndim;
+                    (*view).shape = shape as *mut _;
+                }
+                None => {
+                    (*view).ndim = 1;
+                    (*view).shape = ptr::null_mut();
+                }
+            }
+            (*view).strides = layout.strides.map_or(ptr::null_mut(), |strides| strides as *mut _);
+        }
+        Ok(())
+    }
+
+    fn bf_releasebuffer(_slf: PyRefMut<Self>, _view: *mut ffi::Py_buffer) {}
+}
+
+#[cfg(test)]
+mod test {
+    use super::Frame;
+    use crate::julia::complex::Complex;
+    use pyo3::ffi;
+    use std::slice;
+
+    // the item at the index as numpy would read it through the strides
+    fn item(frame: &Frame, index: &[isize]) -> Vec<u8> {
+        let layout = frame.layout(ffi::PyBUF_RECORDS_RO).unwrap();
+        let strides = unsafe { slice::from_raw_parts(layout.strides.unwrap(), index.len()) };
+        let offset: isize = index.iter().zip(strides).map(|(i, stride)| i * stride).sum();
+        let bytes = unsafe { slice::from_raw_parts(layout.buf as *const u8, layout.len as usize) };
+        bytes[offset as usize..(offset + layout.itemsize) as usize].to_vec()
+    }
+
+    #[test]
+    fn shapes_and_strides() {
+        let cases = [
+            (Frame::rgb(4, 2, vec![0; 24]), vec![2, 4, 3], vec![12, 3, 1], 1, b"B\0"),
+            (Frame::counts(4, 2, vec![0; 8]), vec![2, 4], vec![16, 4], 4, b"I\0"),
+            (Frame::values(4, 2, vec![0.0; 8]), vec![2, 4], vec![32, 8], 8, b"d\0"),
+            (Frame::complex(4, 2, vec![Complex::null(); 8]), vec![2, 4, 2], vec![64, 16, 8], 8, b"d\0"),
+        ];
+        for (frame, shape, strides, itemsize, format) in cases {
+            let layout = frame.layout(ffi::PyBUF_RECORDS_RO).unwrap();
+            let (shape_ptr, ndim) = layout.shape.unwrap();
+            assert_eq!(unsafe { slice::from_raw_parts(shape_ptr, ndim as usize) }, &shape[..]);
+            assert_eq!(unsafe { slice::from_raw_parts(layout.strides.unwrap(), ndim as usize) }, &strides[..]);
+            assert_eq!((layout.itemsize, layout.format), (itemsize, Some(&format[..])));
+            assert_eq!(layout.len, shape.iter().product::<isize>() * itemsize);
+        }
+    }
+
+    #[test]
+    fn rows_go_from_top_to_bottom() {
+        // pixel (x, y) has red x and green y
+        let (width, height) = (3u32, 2u32);
+        let pixels = (0..height).flat_map(|y| (0..width).flat_map(move |x| [x as u8, y as u8, 7])).collect();
+        let frame = Frame::rgb(width, height, pixels);
+        assert_eq!(item(&frame, &[1, 2, 0]), vec![2]);
+        assert_eq!(item(&frame, &[1, 2, 1]), vec![1]);
+        assert_eq!(item(&frame, &[0, 1, 2]), vec![7]);
+
+        let frame = Frame::counts(width, height, (0..6).collect());
+        assert_eq!(item(&frame, &[1, 0]), 3u32.to_ne_bytes().to_vec());
+        let frame = Frame::complex(2, 1, vec![Complex::new(1.0, 2.0), Complex::new(3.0, 4.0)]);
+        assert_eq!(item(&frame, &[0, 1, 1]), 4.0f64.to_ne_bytes().to_vec());
+    }
+
+    #[test]
+    fn buffer_requests() {
+        let frame = Frame::counts(4, 2, vec![0; 8]);
+        // plain bytes for bytes() and memoryview casts
+        let layout = frame.layout(ffi::PyBUF_SIMPLE).unwrap();
+        assert!(layout.shape.is_none() && layout.format.is_none());
+        assert_eq!((layout.itemsize, layout.len), (1, 32));
+        // uint32 items can't be read as bytes with a shape
+        assert!(frame.layout(ffi::PyBUF_ND).is_err());
+        assert!(frame.layout(ffi::PyBUF_WRITABLE).is_err());
+        assert!(Frame::rgb(4, 2, vec![0; 24]).layout(ffi::PyBUF_ND).is_ok());
+    }
+}
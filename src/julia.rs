@@ -1,191 +1,1358 @@
 pub mod complex;
 use complex::Complex;
 
+#[allow(dead_code)]
 pub mod polar;
 pub mod colors;
+pub mod perturbation;
+pub mod floatexp;
+use floatexp::{FloatExp, ComplexExp};
+pub mod series;
+pub mod view;
+pub mod subdivide;
+pub mod field;
+use field::Field;
+pub mod interior;
+use interior::Interior;
+pub mod trap;
+use trap::Trap;
+pub mod average;
+use average::{Average, Accumulator};
+pub mod formula;
+use formula::{Formula, Exponent};
+pub mod polynomial;
+use polynomial::Polynomial;
+pub mod newton;
+use newton::{RootFinder, Converged, Newton, Nova};
+pub mod family;
+use family::{Family, FamilyPlane, FamilyDynamic};
+use colors::{Palette, Coloring, Lighting};
 
-use image::{RgbImage, Rgb, ImageBuffer};
-use std::sync::mpsc;
+use dashu_float::FBig;
+use image::{RgbImage, ImageBuffer};
+use std::sync::{mpsc, Arc};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use threadpool::ThreadPool;
 
+// default radius points have to leave to count as escaped
+pub const ESCAPE_RADIUS: f64 = 2.0;
+
+// orbits that come back closer than this to a saved value count as periodic
+const PERIOD_TOLERANCE: f64 = 1e-14;
+
+// norm used to measure z against the escape radius
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Bailout {
+    Euclidean,
+    Manhattan,
+    Max,
+    Real,
+    Imag,
+}
+
+impl Bailout {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "euclidean" => Some(Bailout::Euclidean),
+            "manhattan" => Some(Bailout::Manhattan),
+            "max" => Some(Bailout::Max),
+            "real" => Some(Bailout::Real),
+            "imag" => Some(Bailout::Imag),
+            _ => None,
+        }
+    }
+
+    pub fn norm(&self, z: Complex) -> f64 {
+        match self {
+            Bailout::Euclidean => z.dist_from_origin(),
+            Bailout::Manhattan => z.real().abs() + z.imag().abs(),
+            Bailout::Max => z.real().abs().max(z.imag().abs()),
+            Bailout::Real => z.real().abs(),
+            Bailout::Imag => z.imag().abs(),
+        }
+    }
+
+    // orbits that overflow count as escaped too, the real and imag norms don't notice the other part exploding
+    pub fn escaped(&self, z: Complex, radius: f64) -> bool {
+        let norm = self.norm(z);
+        norm > radius || norm.is_nan()
+    }
+}
+
+// how the pixels of a frame get computed
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Strategy {
+    // every pixel on its own
+    Brute,
+    // Mariani-Silver: only the borders of rectangles, uniform rectangles get filled
+    Subdivide,
+}
+
+impl Strategy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "brute" => Some(Strategy::Brute),
+            "subdivide" => Some(Strategy::Subdivide),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderOptions {
+    pub tries: u32,
+    pub power: u32,
+    // color by the fractional escape value instead of the iteration count
+    pub smooth: bool,
+    pub radius: f64,
+    pub bailout: Bailout,
+    // stop iterating interior points once their orbit is found to be periodic
+    pub periodicity: bool,
+    pub strategy: Strategy,
+    pub palette: Palette,
+    pub coloring: Coloring,
+    // track dz/dc (dz/dz0 for julia sets) for distance estimates, the distance colorings turn it on anyway
+    pub derivative: bool,
+    // shading of the colors as a relief, none if it's flat
+    pub lighting: Option<Lighting>,
+    pub interior: Interior,
+    // orbit trap the closest approach gets recorded for, see Coloring::Trap
+    pub trap: Option<Trap>,
+    // turns of the stripes per turn of arg z for Coloring::Stripe
+    pub stripes: f64,
+    // what PlotWindow renders, perturbation only knows the mandelbrot formula
+    pub formula: Formula,
+    // renders the polynomial instead of the formula if there is one
+    pub polynomial: Option<Arc<Polynomial>>,
+    // renders the basins of a root finding method instead of either
+    pub newton: Option<Arc<RootFinder>>,
+    // real or complex exponent for the formulas, takes the place of power if set
+    pub exponent: Option<Complex>,
+    // renders one of the phoenix, magnet or lambda maps instead of the formula or polynomial
+    pub family: Option<Family>,
+    // weight of the previous z in Family::Phoenix
+    pub phoenix: Complex,
+}
+
+impl RenderOptions {
+    pub fn new(tries: u32, power: u32) -> Self {
+        RenderOptions {
+            tries,
+            power,
+            smooth: false,
+            radius: ESCAPE_RADIUS,
+            bailout: Bailout::Euclidean,
+            periodicity: true,
+            strategy: Strategy::Brute,
+            palette: Palette::default(),
+            coloring: Coloring::Cycle,
+            derivative: false,
+            lighting: None,
+            interior: Interior::Black,
+            trap: None,
+            stripes: 5.0,
+            formula: Formula::Mandelbrot,
+            polynomial: None,
+            newton: None,
+            exponent: None,
+            family: None,
+            phoenix: Complex::new(-0.5, 0.0),
+        }
+    }
+
+    // what z gets raised to in the formulas
+    pub fn raised_to(&self) -> Exponent {
+        match self.exponent {
+            Some(exponent) => Exponent::Complex(exponent),
+            None => Exponent::Power(self.power),
+        }
+    }
+
+    // normalized iteration count of the escape, see Escape::smooth
+    pub fn smooth_count(&self, escape: &Escape) -> f64 {
+        let degree = self.family.map_or(self.raised_to().degree(), |family| family.degree(self));
+        escape.smooth(self.radius, self.bailout, degree)
+    }
+
+    pub fn escaped(&self, z: Complex) -> bool {
+        self.bailout.escaped(z, self.radius)
+    }
+
+    // periodicity checking or an interior coloring that needs the cycle
+    fn detects_cycles(&self) -> bool {
+        self.periodicity || self.interior.needs_cycle()
+    }
+
+    pub fn tracks_derivative(&self) -> bool {
+        self.derivative || self.coloring.needs_derivative()
+    }
+
+    // orbit statistic the coloring averages, if any
+    pub fn average(&self) -> Option<Average> {
+        match self.coloring {
+            Coloring::Stripe => Some(Average::Stripe(self.stripes)),
+            Coloring::Triangle => Some(Average::Triangle),
+            _ => None,
+        }
+    }
+
+    // the cardioid and bulb shortcut only holds if the whole set stays inside the bailout,
+    // orbits of the set stay within |z| <= 2 which manhattan measures as up to 2 * sqrt(2)
+    fn contains_set(&self) -> bool {
+        match self.bailout {
+            Bailout::Manhattan => self.radius >= 2.0 * std::f64::consts::SQRT_2,
+            _ => self.radius >= 2.0,
+        }
+    }
+
+    // position of the escape on the palette
+    pub fn escape_value(&self, escape: &Escape) -> f64 {
+        if self.smooth {
+            self.smooth_count(escape)
+        } else {
+            escape.iterations as f64
+        }
+    }
+}
+
+// result of iterating a single point
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Escape {
+    // iteration the point escaped in, tries if it never did
+    pub iterations: u32,
+    // last value of the orbit
+    pub z: Complex,
+    // length of the cycle the orbit fell into, if one was detected
+    pub period: Option<u32>,
+    // dz/dc (dz/dz0 for julia sets) at the last value, null if it wasn't tracked
+    pub derivative: Complex,
+    // what the interior coloring needs besides z and the period, see Interior::value
+    pub interior: Option<f64>,
+    // closest the orbit came to the trap, None without a trap or if it never got caught
+    pub trap: Option<f64>,
+    // orbit average without and with the escaping iterate, see average::interpolate
+    pub average: Option<(f64, f64)>,
+    // root finding methods converge in the iteration instead of escaping
+    pub converged: Option<Converged>,
+}
+
+impl Escape {
+    pub fn new(iterations: u32, z: Complex) -> Self {
+        Escape { iterations, z, period: None, derivative: Complex::null(), interior: None, trap: None, average: None, converged: None }
+    }
+
+    // interior point that was caught in a cycle before running out of tries
+    pub fn periodic(tries: u32, z: Complex, period: u32) -> Self {
+        Escape { iterations: tries, z, period: Some(period), derivative: Complex::null(), interior: None, trap: None, average: None, converged: None }
+    }
+
+    pub fn with_derivative(self, derivative: Complex) -> Self {
+        Escape { derivative, ..self }
+    }
+
+    pub fn with_trap(self, trap: Option<f64>) -> Self {
+        Escape { trap, ..self }
+    }
+
+    pub fn with_average(self, average: Option<&Accumulator>) -> Self {
+        Escape { average: average.and_then(|average| average.result()), ..self }
+    }
+
+    pub fn with_converged(self, converged: Converged) -> Self {
+        Escape { converged: Some(converged), ..self }
+    }
+
+    // interior point of z^d + c, minimum is the smallest |z| of the orbit
+    fn inside(self, c: Complex, in_plane: bool, minimum: f64, options: &RenderOptions) -> Self {
+        let interior = options.interior.value(self.z, c, in_plane, self.period, minimum, options.power);
+        Escape { interior, ..self }
+    }
+
+    // exterior distance estimate |z| ln|z| / |dz|, in the units the derivative was taken in.
+    // Only accurate with a large escape radius, None without a derivative
+    pub fn distance(&self) -> Option<f64> {
+        let z = self.z.dist_from_origin();
+        let dz = self.derivative.dist_from_origin();
+        if dz == 0.0 || z <= 1.0 || z.is_nan() || dz.is_nan() {
+            return None;
+        }
+        Some(0.5 * z * z.ln() / dz)
+    }
+
+    // normalized iteration count: the fraction comes from how far z overshot the escape radius,
+    // which makes it continuous across the bands of equal iteration counts.
+    // Converged points have their own fraction
+    // d is the degree of z^d + c, real for the exponents that aren't whole
+    pub fn smooth(&self, radius: f64, bailout: Bailout, d: f64) -> f64 {
+        if let Some(converged) = self.converged {
+            return converged.speed;
+        }
+        let log_z = bailout.norm(self.z).ln();
+        if d <= 1.0 || log_z <= 0.0 || !log_z.is_finite() || radius <= 1.0 {
+            return self.iterations as f64;
+        }
+        let frac = (log_z / radius.ln()).ln() / d.ln();
+        self.iterations as f64 + 1.0 - frac.clamp(0.0, 1.0)
+    }
+}
+
 pub trait Fractal {
     // function that returns how stable the given cords are for the fractal
-    fn stable_cords(&self, x: f64, y: f64, tries:u32, power: u32) -> u32 {
+    fn stable_cords(&self, x: f64, y: f64, options: &RenderOptions) -> Escape {
         let con = Complex::new(x, y);
-        self.stable(con, tries, power)
+        self.stable(con, options)
     }
 
-    fn stable(&self, start: Complex, tries: u32, power: u32) -> u32;
+    fn stable(&self, start: Complex, options: &RenderOptions) -> Escape;
 }
 
 pub type Julia = Complex;   //represents starting point
 
 impl Fractal for Julia {
-    fn stable(&self, start: Complex, tries: u32, power: u32) -> u32 {
-        let mut z = start;
-        for i in 0..tries {
-            z = z.powi(power) + self.clone();
-            if z.dist_from_origin() > 2.0 {
-                return i;
-            }
-        }
-        return tries;
+    fn stable(&self, start: Complex, options: &RenderOptions) -> Escape {
+        orbit(&FormulaStep::new(Formula::Mandelbrot, false, options), start, *self, false, options)
     }
 }
 
-/*
-// Problem: can't transfer PolyJulia between threads
-struct PolyJulia {
-    factors: Vec<Complex>
+// parameter plane of a formula: the pixel is c and the orbit starts at 0
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Plane(pub Formula);
+
+impl Fractal for Plane {
+    fn stable(&self, c: Complex, options: &RenderOptions) -> Escape {
+        match self.0 {
+            Formula::Mandelbrot => mandelbrot(c.real(), c.imag(), options),
+            formula => orbit(&FormulaStep::new(formula, true, options), Complex::null(), c, true, options),
+        }
+    }
 }
 
-impl PolyJulia {
+// dynamic plane of a formula for a fixed c, like Julia: the pixel is the start of the orbit
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Dynamic(pub Formula, pub Complex);
 
-    pub fn new(factors: Vec<Complex>) -> Self {
-        Self{factors: factors}
+impl Fractal for Dynamic {
+    fn stable(&self, start: Complex, options: &RenderOptions) -> Escape {
+        orbit(&FormulaStep::new(self.0, false, options), start, self.1, false, options)
     }
 }
 
+// julia set of a polynomial, shared between the render threads
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolyJulia(pub Arc<Polynomial>);
+
 impl Fractal for PolyJulia {
-    fn stable(&self, start: Complex, tries: u32) -> u32 {
-        let mut z = start;
-        for i in 0..tries {
-            for (j, c) in self.factors.iter().enumerate() {
-                z += c.clone() * z.powi(j as u32);
-            }
-            // not sure if this is true for PolyJulias :/
-            if z.dist_from_origin() > 2.0 {
-                return i;
-            }
+    fn stable(&self, start: Complex, options: &RenderOptions) -> Escape {
+        let step = PolynomialStep { polynomial: &self.0, c: None };
+        orbit(&step, start, self.0.coefficient(0, None), false, options)
+    }
+}
+
+// parameter plane of a polynomial: the pixel is its parameter coefficient and the orbit starts at 0,
+// which is a critical point only without a linear term
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolyMandelbrot(pub Arc<Polynomial>);
+
+impl Fractal for PolyMandelbrot {
+    fn stable(&self, c: Complex, options: &RenderOptions) -> Escape {
+        let step = PolynomialStep { polynomial: &self.0, c: Some(c) };
+        orbit(&step, Complex::null(), self.0.coefficient(0, Some(c)), true, options)
+    }
+}
+
+// what the kernel iterates: z -> f(z) + c
+trait Step {
+    // previous is the iterate before z, zero at the start. Only phoenix looks at it
+    fn apply(&self, z: Complex, previous: Complex) -> Complex;
+
+    // derivative after the step, with respect to the pixel. previous is the derivative before dz
+    fn derivative(&self, z: Complex, dz: Complex, previous: Complex) -> Complex;
+
+    // every orbit that gets further out escapes, on top of the radius of the options
+    fn radius(&self) -> f64 {
+        0.0
+    }
+
+    // the cycle derivatives of the interior colorings only hold for z^d + c
+    fn is_power(&self) -> bool {
+        false
+    }
+
+    // fixed point the orbits converge to when they don't escape, like 1 for the magnet maps
+    fn attractor(&self) -> Option<Complex> {
+        None
+    }
+}
+
+struct FormulaStep {
+    formula: Formula,
+    exponent: Exponent,
+    // derivative of c with respect to the pixel
+    dc: Complex,
+}
+
+impl FormulaStep {
+    fn new(formula: Formula, in_plane: bool, options: &RenderOptions) -> Self {
+        let dc = if in_plane { Complex::new(1.0, 0.0) } else { Complex::null() };
+        FormulaStep { formula, exponent: options.raised_to(), dc }
+    }
+}
+
+impl Step for FormulaStep {
+    fn apply(&self, z: Complex, _: Complex) -> Complex {
+        self.formula.apply(z, self.exponent)
+    }
+
+    fn derivative(&self, z: Complex, dz: Complex, _: Complex) -> Complex {
+        self.formula.derivative(z, dz, self.exponent) + self.dc
+    }
+
+    fn is_power(&self) -> bool {
+        self.formula == Formula::Mandelbrot && matches!(self.exponent, Exponent::Power(_))
+    }
+}
+
+// p(z) without its constant term, which is the c of the orbit
+struct PolynomialStep<'a> {
+    polynomial: &'a Polynomial,
+    // parameter coefficient in the parameter plane
+    c: Option<Complex>,
+}
+
+impl Step for PolynomialStep<'_> {
+    fn apply(&self, z: Complex, _: Complex) -> Complex {
+        self.polynomial.evaluate(z, self.c).0 - self.polynomial.coefficient(0, self.c)
+    }
+
+    fn derivative(&self, z: Complex, dz: Complex, _: Complex) -> Complex {
+        let derivative = self.polynomial.evaluate(z, self.c).1 * dz;
+        match self.c {
+            // p depends on its parameter coefficient a_k through z^k
+            Some(_) => derivative + pow(z, self.polynomial.parameter() as u32),
+            None => derivative,
         }
-        return tries;
     }
-} 
-*/
 
-pub fn mandelbrot(cx: f64, cy: f64, tries: u32, power: u32) -> u32 {
+    fn radius(&self) -> f64 {
+        self.polynomial.radius(self.c)
+    }
+}
+
+// chain rule step of the iteration: the derivative of z^d at z times dz
+pub fn derivative(z: Complex, dz: Complex, power: u32) -> Complex {
+    let d = perturbation::degree(power);
+    if d == 0 {
+        return Complex::null();
+    }
+    pow(z, d - 1) * dz * Complex::new(d as f64, 0.0)
+}
+
+// z^k, Complex::powi(p) is z^(p + 1)
+pub fn pow(z: Complex, k: u32) -> Complex {
+    match k {
+        0 => Complex::new(1.0, 0.0),
+        1 => z,
+        _ => z.powi(k - 1),
+    }
+}
+
+// Brent's cycle detection: z is compared to a saved value that moves ahead after doubling intervals,
+// so any cycle gets caught once the interval is longer than both the period and the lead-in
+struct CycleCheck {
+    saved: Complex,
+    steps: u32,
+    interval: u32,
+}
+
+impl CycleCheck {
+    fn new(z: Complex) -> Self {
+        CycleCheck { saved: z, steps: 0, interval: 1 }
+    }
+
+    // period of the cycle once z came back to the saved value
+    fn update(&mut self, z: Complex) -> Option<u32> {
+        self.steps += 1;
+        if (z - self.saved).dist_from_origin() < PERIOD_TOLERANCE {
+            return Some(self.steps);
+        }
+        if self.steps == self.interval {
+            self.saved = z;
+            self.steps = 0;
+            self.interval = self.interval.saturating_mul(2);
+        }
+        None
+    }
+}
+
+pub fn mandelbrot(cx: f64, cy: f64, options: &RenderOptions) -> Escape {
     let c = Complex::new(cx, cy);
-    let mut z = Complex::null();
-    for i in 0..tries {
-        z = z.powi(power) + c;
-        if z.dist_from_origin() > 2.0 {
-            return i 
+    // the shortcut doesn't iterate, it only knows the period
+    if options.periodicity && options.interior.is_uniform() && options.trap.is_none() && options.raised_to() == Exponent::Power(1) && options.contains_set() {
+        if in_cardioid(cx, cy) {
+            return Escape::periodic(options.tries, c, 1);
+        }
+        if in_bulb(cx, cy) {
+            return Escape::periodic(options.tries, c, 2);
+        }
+    }
+    orbit(&FormulaStep::new(Formula::Mandelbrot, true, options), Complex::null(), c, true, options)
+}
+
+// iterates z -> f(z) + c from start until it escapes or falls into a cycle. in_plane means the pixel is c,
+// the derivative is dz/dc then instead of dz/dstart
+fn orbit<S: Step>(step: &S, start: Complex, c: Complex, in_plane: bool, options: &RenderOptions) -> Escape {
+    let mut z = start;
+    let mut dz = if in_plane { Complex::null() } else { Complex::new(1.0, 0.0) };
+    let radius = options.radius.max(step.radius());
+    let track = options.tracks_derivative();
+    let mut minimum = f64::INFINITY;
+    let track_minimum = options.interior == Interior::Minimum;
+    let mut trapped = None;
+    let mut average = options.average().map(Accumulator::new);
+    let mut cycle = CycleCheck::new(z);
+    let (mut previous, mut previous_dz) = (Complex::null(), Complex::null());
+    // distance to the attractor after the last step
+    let mut last = f64::INFINITY;
+    let inside = |escape: Escape, minimum: f64| {
+        if step.is_power() || options.interior == Interior::Minimum {
+            escape.inside(c, in_plane, minimum, options)
+        } else {
+            escape
+        }
+    };
+    for i in 0..options.tries {
+        if track {
+            let next = step.derivative(z, dz, previous_dz);
+            previous_dz = dz;
+            dz = next;
+        }
+        let zd = step.apply(z, previous);
+        previous = z;
+        z = zd + c;
+        if let Some(average) = &mut average {
+            average.add(zd, c, z);
+        }
+        if options.bailout.escaped(z, radius) {
+            return Escape::new(i, z).with_derivative(dz).with_trap(trapped).with_average(average.as_ref());
+        }
+        if let Some(attractor) = step.attractor() {
+            let distance = (z - attractor).dist_from_origin();
+            if distance < newton::TOLERANCE {
+                let converged = Converged { root: Some(0), speed: newton::speed(i, last, distance) };
+                return Escape::new(i, z).with_derivative(dz).with_trap(trapped).with_converged(converged);
+            }
+            last = distance;
+        }
+        if track_minimum {
+            minimum = minimum.min(z.dist_from_origin());
+        }
+        if let Some(trap) = &options.trap {
+            trapped = trap.closest(trapped, z);
+        }
+        if options.detects_cycles() {
+            if let Some(period) = cycle.update(z) {
+                return inside(Escape::periodic(options.tries, z, period), minimum).with_trap(trapped);
+            }
         }
     }
-    tries
+    inside(Escape::new(options.tries, z), minimum).with_trap(trapped)
+}
+
+// main cardioid of z^2 + c, all of its points are attracted to a fixed point
+fn in_cardioid(cx: f64, cy: f64) -> bool {
+    let x = cx - 0.25;
+    let q = x * x + cy * cy;
+    q * (q + x) <= 0.25 * cy * cy
+}
+
+// period 2 bulb of z^2 + c, the circle of radius 1/4 around -1
+fn in_bulb(cx: f64, cy: f64) -> bool {
+    let x = cx + 1.0;
+    x * x + cy * cy <= 0.0625
+}
+
+// distance between neighbouring cords of convert_range
+fn pixel_size(min: f64, max: f64, slices: u32) -> f64 {
+    (max - min) / (slices.max(2) - 1) as f64
 }
 
 fn convert_range(min: f64, max: f64, slices: u32) -> polar::Iter<(u32, f64)> {
-    let dif = max - min;
     Box::new(
         (0..slices).map(move |i| {
-            (i, min + (i as f64 / (slices -1) as f64) * dif)
+            (i, slice_cord(min, max, slices, i))
         })
     )
 }
 
-pub fn single_julia(jx: f64, jy: f64, scale: u32, out_file: &str, tries: u32, power: u32) {
+// cord of the i-th of `slices` evenly spaced points from min to max
+fn slice_cord(min: f64, max: f64, slices: u32, i: u32) -> f64 {
+    min + (i as f64 / (slices -1) as f64) * (max - min)
+}
+
+pub fn single_julia(jx: f64, jy: f64, scale: u32, out_file: &str, options: &RenderOptions) -> Vec<u8> {
     let jul = Julia::new(jx, jy);
-    main_julia(jul, -X_DIF, X_DIF, -Y_DIF, Y_DIF, 16 * scale , 9 * scale, out_file, tries, power)
+    main_julia(jul, -X_DIF, X_DIF, -Y_DIF, Y_DIF, 16 * scale , 9 * scale, out_file, options)
 }
 
-pub fn raw_single_julia(jx: f64, jy: f64, scale: u32, tries: u32, power: u32) -> Vec<u8> {
+pub fn raw_single_julia(jx: f64, jy: f64, scale: u32, options: &RenderOptions) -> Vec<u8> {
     let jul = Julia::new(jx, jy);
-    raw_julia(jul, -X_DIF, X_DIF, -Y_DIF, Y_DIF, 16 * scale , 9 * scale, tries, power)
+    raw_julia(jul, -X_DIF, X_DIF, -Y_DIF, Y_DIF, 16 * scale , 9 * scale, options)
 }
 
 pub const X_DIF: f64 = 2.1333;
 pub const Y_DIF: f64 = 1.2;
 
-pub fn main_julia(julia: Julia, x_min: f64, x_max: f64, y_min:f64, y_max: f64, x_range: u32, y_range: u32, out_file: &str, tries: u32, power: u32) {
-    let img = render_julia(julia, x_min, x_max, y_min, y_max, x_range, y_range, tries, power);
+// saves the image and returns its pixels like raw_julia
+pub fn main_julia(julia: Julia, x_min: f64, x_max: f64, y_min:f64, y_max: f64, x_range: u32, y_range: u32, out_file: &str, options: &RenderOptions) -> Vec<u8> {
+    let img = render_julia(julia, x_min, x_max, y_min, y_max, x_range, y_range, options, Watch::default());
     img.save(out_file).expect("could not save image");
+    img.into_raw()
 }
 
-pub fn raw_julia(julia: Julia, x_min: f64, x_max: f64, y_min:f64, y_max: f64, x_range: u32, y_range: u32, tries: u32, power: u32) -> Vec<u8> {
-    let img = render_julia(julia, x_min, x_max, y_min, y_max, x_range, y_range, tries, power);
+pub fn raw_julia(julia: Julia, x_min: f64, x_max: f64, y_min:f64, y_max: f64, x_range: u32, y_range: u32, options: &RenderOptions) -> Vec<u8> {
+    let img = render_julia(julia, x_min, x_max, y_min, y_max, x_range, y_range, options, Watch::default());
     img.into_raw()
 }
 
-fn render_julia(julia: Julia, x_min: f64, x_max: f64, y_min:f64, y_max: f64, x_range: u32, y_range: u32, tries: u32, power: u32) -> ImageBuffer<image::Rgb<u8>, std::vec::Vec<u8>> {
+pub fn julia_field(julia: Julia, x_min: f64, x_max: f64, y_min:f64, y_max: f64, x_range: u32, y_range: u32, options: &RenderOptions) -> Field {
+    field(x_min, x_max, y_min, y_max, x_range, y_range, options, Arc::default(), move |x, y, options| {
+        julia.stable_cords(x, y, options)
+    })
+}
+
+fn render_julia(julia: Julia, x_min: f64, x_max: f64, y_min:f64, y_max: f64, x_range: u32, y_range: u32, options: &RenderOptions, watch: Watch) -> ImageBuffer<image::Rgb<u8>, std::vec::Vec<u8>> {
+    render(x_min, x_max, y_min, y_max, x_range, y_range, options, watch, move |x, y, options| {
+        julia.stable_cords(x, y, options)
+    })
+}
+
+// lets other threads follow and cancel a running render
+#[derive(Debug, Default)]
+pub struct Control {
+    cancelled: AtomicBool,
+    done: AtomicU64,
+    total: AtomicU64,
+}
+
+impl Control {
+    // workers stop before their next pixel, the pixels they didn't get to stay black
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    // fraction of the pixels that are done
+    pub fn progress(&self) -> f64 {
+        let total = self.total.load(Ordering::Relaxed);
+        if total == 0 {
+            0.0
+        } else {
+            self.done.load(Ordering::Relaxed) as f64 / total as f64
+        }
+    }
+
+    fn start(&self, pixels: u64) {
+        self.done.store(0, Ordering::Relaxed);
+        self.total.store(pixels, Ordering::Relaxed);
+    }
+
+    fn advance(&self, pixels: u64) {
+        self.done.fetch_add(pixels, Ordering::Relaxed);
+    }
+}
+
+// what the caller gets to see of a render while it runs
+#[derive(Default)]
+pub struct Watch<'a> {
+    // gets the whole frame after every pass, the render is progressive if set
+    pub preview: Option<&'a mut dyn FnMut(&RgbImage)>,
+    pub control: Arc<Control>,
+}
+
+// colors every pixel by the escape `stable` reports for its cords,
+// renders progressively if there is someone to show the passes to
+fn render<F>(x_min: f64, x_max: f64, y_min:f64, y_max: f64, x_range: u32, y_range: u32, options: &RenderOptions, watch: Watch, stable: F) -> ImageBuffer<image::Rgb<u8>, std::vec::Vec<u8>>
+where F: Fn(f64, f64, &RenderOptions) -> Escape + Send + Sync + 'static {
+    watch.control.start(x_range as u64 * y_range as u64);
+    render_started(x_min, x_max, y_min, y_max, x_range, y_range, options, watch, stable)
+}
+
+// render with the progress already started, deep windows count their reference orbit in too
+fn render_started<F>(x_min: f64, x_max: f64, y_min:f64, y_max: f64, x_range: u32, y_range: u32, options: &RenderOptions, watch: Watch, stable: F) -> ImageBuffer<image::Rgb<u8>, std::vec::Vec<u8>>
+where F: Fn(f64, f64, &RenderOptions) -> Escape + Send + Sync + 'static {
+    let control = watch.control;
+    if let Some(preview) = watch.preview {
+        return render_progressive(x_min, x_max, y_min, y_max, x_range, y_range, options, preview, control, stable);
+    }
+    let columns = escapes(x_min, x_max, y_min, y_max, x_range, y_range, options, control, false, stable);
+    colors::color_field(&Field::from_columns(columns, pixel_size(x_min, x_max, x_range), options), options)
+}
+
+// escapes of every pixel as columns, computed with the strategy the options ask for.
+// If exact, subdivision never fills escaping pixels, only the iteration count of a copy would be right
+fn escapes<F>(x_min: f64, x_max: f64, y_min:f64, y_max: f64, x_range: u32, y_range: u32, options: &RenderOptions, control: Arc<Control>, exact: bool, stable: F) -> Vec<Vec<Escape>>
+where F: Fn(f64, f64, &RenderOptions) -> Escape + Send + Sync + 'static {
+    match options.strategy {
+        Strategy::Brute => brute_force(x_min, x_max, y_min, y_max, x_range, y_range, options, control, stable),
+        Strategy::Subdivide => subdivide::escapes(x_min, x_max, y_min, y_max, x_range, y_range, options, control, exact, stable),
+    }
+}
+
+// fields hand out the smooth values and final z of every pixel, so they are computed exactly
+fn field<F>(x_min: f64, x_max: f64, y_min:f64, y_max: f64, x_range: u32, y_range: u32, options: &RenderOptions, control: Arc<Control>, stable: F) -> Field
+where F: Fn(f64, f64, &RenderOptions) -> Escape + Send + Sync + 'static {
+    let columns = escapes(x_min, x_max, y_min, y_max, x_range, y_range, options, control, true, stable);
+    Field::from_columns(columns, pixel_size(x_min, x_max, x_range), options)
+}
+
+// distance between computed pixels in each pass, every pass only computes the pixels the coarser ones left out
+const PASSES: [u32; 3] = [4, 2, 1];
+
+// renders every 4th pixel in both directions (1/16 of the frame), then every 2nd (1/4), then the rest.
+// Pixels stand in for the block up to the next computed pixel until a finer pass replaces them.
+fn render_progressive<F>(x_min: f64, x_max: f64, y_min:f64, y_max: f64, x_range: u32, y_range: u32, options: &RenderOptions, preview: &mut dyn FnMut(&RgbImage), control: Arc<Control>, stable: F) -> ImageBuffer<image::Rgb<u8>, std::vec::Vec<u8>>
+where F: Fn(f64, f64, &RenderOptions) -> Escape + Send + Sync + 'static {
+    // pixels no pass got to stay black like the interior
+    let mut columns = vec![vec![Escape::new(options.tries, Complex::null()); y_range as usize]; x_range as usize];
     let mut img = RgbImage::new(x_range, y_range);
+    let stable = Arc::new(stable);
+    let options = Arc::new(options.clone());
+    let pool = ThreadPool::new(WORKERS);
+
+    for (pass, step) in PASSES.iter().copied().enumerate() {
+        if control.is_cancelled() {
+            break;
+        }
+        // pixels on the grid of the previous pass are done already
+        let done = if pass == 0 { None } else { Some(PASSES[pass - 1]) };
+        let mut recievers = Vec::new();
+        for (x, cord_x) in convert_range(x_min, x_max, x_range).filter(|(x, _)| x % step == 0) {
+            let (tx, rx) = mpsc::channel();
+            recievers.push((x, rx));
+            let stable = stable.clone();
+            let options = options.clone();
+            let control = control.clone();
+            pool.execute(move || {
+                let line: Vec<(u32, Escape)> = convert_range(y_min, y_max, y_range)
+                    .filter(|(y, _)| y % step == 0 && !done.is_some_and(|done| x % done == 0 && y % done == 0))
+                    .take_while(|_| !control.is_cancelled())
+                    .map(|(y, cord_y)| (y, stable(cord_x, cord_y, &options)))
+                    .collect();
+                control.advance(line.len() as u64);
+                tx.send(line).unwrap();
+            });
+        }
+
+        for (x, rx) in recievers {
+            for (y, escape) in rx.recv().unwrap() {
+                for column in &mut columns[x as usize..(x + step).min(x_range) as usize] {
+                    for pixel in &mut column[y as usize..(y + step).min(y_range) as usize] {
+                        *pixel = escape;
+                    }
+                }
+            }
+        }
+        // colored as a whole, histogram coloring depends on all the pixels
+        img = colors::color_field(&Field::from_columns(columns.clone(), pixel_size(x_min, x_max, x_range), &options), &options);
+        preview(&img);
+    }
+    pool.join();
+
+    img
+}
+
+// escapes of every pixel, one column per job
+fn brute_force<F>(x_min: f64, x_max: f64, y_min:f64, y_max: f64, x_range: u32, y_range: u32, options: &RenderOptions, control: Arc<Control>, stable: F) -> Vec<Vec<Escape>>
+where F: Fn(f64, f64, &RenderOptions) -> Escape + Send + Sync + 'static {
     let mut recievers = Vec::new();
+    let stable = Arc::new(stable);
+    let options = Arc::new(options.clone());
 
-    let workers = 32; // 2 x cores on my PC
-    let pool = ThreadPool::new(workers);
+    let pool = ThreadPool::new(WORKERS);
     
     for (_, cord_x) in convert_range(x_min, x_max, x_range) {
         let (tx, rx) = mpsc::channel();
         recievers.push(rx);
+        let stable = stable.clone();
+        let options = options.clone();
+        let control = control.clone();
         pool.execute(move || {
-            let mut line = Vec::new();
-            
-            for (_, cord_y) in convert_range(y_min, y_max, y_range) {
-                let i = julia.stable_cords(cord_x, cord_y, tries, power);
-                if i != tries {
-                    line.push(colors::color_builder(i));
-                } else {
-                    line.push(Rgb([0, 0, 0]));
-                }
-            }
-            
+            // a cancelled column ends early, the rest of it stays black
+            let line: Vec<Escape> = convert_range(y_min, y_max, y_range)
+                .take_while(|_| !control.is_cancelled())
+                .map(|(_, cord_y)| stable(cord_x, cord_y, &options))
+                .collect();
+            control.advance(line.len() as u64);
             tx.send(line).unwrap();
         });
     }
     
-    for (x, rx) in recievers.into_iter().enumerate() {
-        let colors = rx.recv().unwrap();
-        for (y, c) in colors.into_iter().enumerate() {
-            img.put_pixel(x as u32, y as u32, c);
-        }
-    }
+    let columns = recievers.into_iter().map(|rx| rx.recv().unwrap()).collect();
     pool.join();
-    
-    img
+    columns
+}
+
+const WORKERS: usize = 32; // 2 x cores on my PC
+
+pub fn main_mandelbrot(scale: u32, out_file: &str, options: &RenderOptions) -> Vec<u8> {
+    fine_mandelbrot(-X_DIF, X_DIF, -Y_DIF, Y_DIF, 16 * scale , 9 * scale, out_file, options)
 }
 
-pub fn main_mandelbrot(scale: u32, out_file: &str, tries: u32, power: u32) {
-    let img = render_mandelbrot(-X_DIF, X_DIF, -Y_DIF, Y_DIF, 16 * scale , 9 * scale, tries, power);
+// saves the image and returns its pixels, rows from top to bottom
+pub fn fine_mandelbrot(x_min: f64, x_max: f64, y_min:f64, y_max: f64, x_range: u32, y_range: u32, out_file: &str, options: &RenderOptions) -> Vec<u8> {
+    let img = render_mandelbrot(x_min, x_max, y_min, y_max, x_range, y_range, options, Watch::default());
     img.save(out_file).expect("could not save image");
+    img.into_raw()
 }
 
-pub fn fine_mandelbrot(x_min: f64, x_max: f64, y_min:f64, y_max: f64, x_range: u32, y_range: u32, out_file: &str, tries: u32, power: u32) {
-    render_mandelbrot(x_min, x_max, y_min, y_max, x_range, y_range, tries, power).save(out_file).expect("could not save image")
+pub fn mandelbrot_field(x_min: f64, x_max: f64, y_min:f64, y_max: f64, x_range: u32, y_range: u32, options: &RenderOptions) -> Field {
+    field(x_min, x_max, y_min, y_max, x_range, y_range, options, Arc::default(), mandelbrot)
 }
 
-fn render_mandelbrot(x_min: f64, x_max: f64, y_min:f64, y_max: f64, x_range: u32, y_range: u32, tries: u32, power: u32) -> ImageBuffer<image::Rgb<u8>, std::vec::Vec<u8>> {
-    let mut img = RgbImage::new(x_range, y_range);
-    let mut recievers = Vec::new();
+fn render_mandelbrot(x_min: f64, x_max: f64, y_min:f64, y_max: f64, x_range: u32, y_range: u32, options: &RenderOptions, watch: Watch) -> ImageBuffer<image::Rgb<u8>, std::vec::Vec<u8>> {
+    render(x_min, x_max, y_min, y_max, x_range, y_range, options, watch, mandelbrot)
+}
 
-    let workers = 32; // 2 x cores on my PC
-    let pool = ThreadPool::new(workers);
-    
-    for (_, cord_x) in convert_range(x_min, x_max, x_range) {
-        let (tx, rx) = mpsc::channel();
-        recievers.push(rx);
-        pool.execute(move || {
-            let mut line = Vec::new();
-            
-            for (_, cord_y) in convert_range(y_min, y_max, y_range) {
-                let i = mandelbrot(cord_x, cord_y, tries, power);
-                if i != tries {
-                    line.push(colors::color_builder(i));
-                } else {
-                    line.push(Rgb([0, 0, 0]));
+// renders the window around (cx, cy) and switches to perturbation once f64 can't resolve the pixels anymore,
+// returns the image and how many iterations the series approximation skipped
+pub fn deep_mandelbrot(cx: &FBig, cy: &FBig, x_dif: FloatExp, y_dif: FloatExp, x_range: u32, y_range: u32, options: &RenderOptions, watch: Watch) -> (RgbImage, u32) {
+    deep_render(None, cx, cy, x_dif, y_dif, x_range, y_range, options, watch)
+}
+
+// same as deep_mandelbrot, (cx, cy) is the center of the window in the dynamic plane
+pub fn deep_julia(julia: Julia, cx: &FBig, cy: &FBig, x_dif: FloatExp, y_dif: FloatExp, x_range: u32, y_range: u32, options: &RenderOptions, watch: Watch) -> (RgbImage, u32) {
+    deep_render(Some(julia), cx, cy, x_dif, y_dif, x_range, y_range, options, watch)
+}
+
+// a cancelled render is incomplete, pixels it didn't get to stay black
+fn deep_render(julia: Option<Julia>, cx: &FBig, cy: &FBig, x_dif: FloatExp, y_dif: FloatExp, x_range: u32, y_range: u32, options: &RenderOptions, watch: Watch) -> (RgbImage, u32) {
+    let pixels = x_range as u64 * y_range as u64;
+    let ((x_min, x_max, y_min, y_max), stable, skipped) = window(julia, cx, cy, x_dif, y_dif, pixels, options, &watch.control);
+    let img = render_started(x_min, x_max, y_min, y_max, x_range, y_range, options, watch, stable);
+    (img, skipped)
+}
+
+// field of the same window deep_mandelbrot and deep_julia render, mandelbrot set if there is no julia
+pub fn deep_field(julia: Option<Julia>, cx: &FBig, cy: &FBig, x_dif: FloatExp, y_dif: FloatExp, x_range: u32, y_range: u32, options: &RenderOptions, control: Arc<Control>) -> (Field, u32) {
+    let pixels = x_range as u64 * y_range as u64;
+    let ((x_min, x_max, y_min, y_max), stable, skipped) = window(julia, cx, cy, x_dif, y_dif, pixels, options, &control);
+    (field(x_min, x_max, y_min, y_max, x_range, y_range, options, control, stable), skipped)
+}
+
+fn is_deep(x_dif: FloatExp, y_dif: FloatExp) -> bool {
+    x_dif < FloatExp::new(perturbation::DEEP_ZOOM_SPAN) || y_dif < FloatExp::new(perturbation::DEEP_ZOOM_SPAN)
+}
+
+type Stable = Box<dyn Fn(f64, f64, &RenderOptions) -> Escape + Send + Sync>;
+
+// bounds and stable function for the pixels of the window around (cx, cy), plus the iterations the series skipped.
+// Deep windows pass offsets from the reference in the center, which stay exact at any zoom.
+// Their kernels record neither orbit traps nor orbit averages, the colorings fall back to the escape value.
+// The other formulas, polynomials and root finding stay in f64 and run out of precision when zoomed in that far.
+// Starts the progress of control, the reference orbit counts like a pixel per iteration and stops when cancelled
+fn window(julia: Option<Julia>, cx: &FBig, cy: &FBig, x_dif: FloatExp, y_dif: FloatExp, pixels: u64, options: &RenderOptions, control: &Control) -> ((f64, f64, f64, f64), Stable, u32) {
+    if !is_deep(x_dif, y_dif) || options.formula != Formula::Mandelbrot || options.polynomial.is_some() || options.newton.is_some() || options.exponent.is_some() || options.family.is_some() {
+        let (cx, cy, x_dif, y_dif) = (cx.to_f64().value(), cy.to_f64().value(), x_dif.to_f64(), y_dif.to_f64());
+        let bounds = (cx - 0.5 * x_dif, cx + 0.5 * x_dif, cy - 0.5 * y_dif, cy + 0.5 * y_dif);
+        control.start(pixels);
+        if let Some(finder) = &options.newton {
+            // without nova the julia point doesn't change anything
+            let stable: Stable = match (julia, finder.nova) {
+                (None, true) => {
+                    let fractal = Nova(finder.clone());
+                    Box::new(move |x, y, options| fractal.stable_cords(x, y, options))
+                }
+                (julia, _) => {
+                    let fractal = Newton(finder.clone(), julia.unwrap_or_else(Complex::null));
+                    Box::new(move |x, y, options| fractal.stable_cords(x, y, options))
                 }
+            };
+            return (bounds, stable, 0);
+        }
+        if let Some(family) = options.family {
+            let stable: Stable = match julia {
+                Some(julia) => Box::new(move |x, y, options| FamilyDynamic(family, julia).stable_cords(x, y, options)),
+                None => Box::new(move |x, y, options| FamilyPlane(family).stable_cords(x, y, options)),
+            };
+            return (bounds, stable, 0);
+        }
+        let stable: Stable = match (julia, &options.polynomial, options.formula) {
+            // the julia point of the parameter plane becomes the parameter coefficient
+            (Some(julia), Some(polynomial), _) => {
+                let fractal = PolyJulia(Arc::new(polynomial.with_parameter(julia)));
+                Box::new(move |x, y, options| fractal.stable_cords(x, y, options))
             }
-            
-            tx.send(line).unwrap();
+            (None, Some(polynomial), _) => {
+                let fractal = PolyMandelbrot(polynomial.clone());
+                Box::new(move |x, y, options| fractal.stable_cords(x, y, options))
+            }
+            (Some(julia), None, Formula::Mandelbrot) => Box::new(move |x, y, options| julia.stable_cords(x, y, options)),
+            (Some(julia), None, formula) => Box::new(move |x, y, options| Dynamic(formula, julia).stable_cords(x, y, options)),
+            (None, None, formula) => Box::new(move |x, y, options| Plane(formula).stable_cords(x, y, options)),
+        };
+        return (bounds, stable, 0);
+    }
+
+    control.start(pixels + options.tries as u64);
+    let reference = match julia {
+        Some(julia) => perturbation::Reference::julia(cx, cy, julia, options, control),
+        None => perturbation::Reference::mandelbrot(cx, cy, options, control),
+    };
+    let series = series::Series::new(&reference, x_dif, y_dif, options.tries, control);
+    let skipped = series.skipped();
+    let floatexp_span = FloatExp::new(perturbation::FLOATEXP_SPAN);
+    // derivatives are taken with respect to the offsets in the window, like the pixel size of the field.
+    // They overflow past the f64 range, the distance colorings fall back to the escape value then
+    let scale = Complex::new(x_dif.to_f64(), 0.0);
+    let stable: Stable = if x_dif < floatexp_span || y_dif < floatexp_span {
+        Box::new(move |rx, ry, options| {
+            let offset = ComplexExp::new(FloatExp::new(rx) * x_dif, FloatExp::new(ry) * y_dif);
+            let escape = series.stable_exp(&reference, offset, options.tries);
+            escape.with_derivative(escape.derivative * scale)
+        })
+    } else {
+        let (x_dif, y_dif) = (x_dif.to_f64(), y_dif.to_f64());
+        Box::new(move |rx, ry, options| {
+            let escape = series.stable(&reference, Complex::new(rx * x_dif, ry * y_dif), options.tries);
+            escape.with_derivative(escape.derivative * scale)
+        })
+    };
+    ((-0.5, 0.5, -0.5, 0.5), stable, skipped)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Escape, RenderOptions, Bailout, Strategy, Fractal, Julia, ESCAPE_RADIUS, mandelbrot, complex::Complex};
+    use super::{render, render_mandelbrot, render_julia, mandelbrot_field, julia_field, colors, Coloring, Interior, Trap, Watch, Control};
+    use super::{Plane, Dynamic, Formula, PolyJulia, PolyMandelbrot, Polynomial, RootFinder, Newton, escapes, Field};
+    use super::newton::Method;
+    use super::{deep_mandelbrot, FloatExp, perturbation::big_from_f64};
+    use super::polar;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn smooth_stays_in_band() {
+        let options = RenderOptions::new(100, 1);
+        for i in 0..50 {
+            let escape = mandelbrot(-2.1 + i as f64 * 0.01, 0.3, &options);
+            let smooth = escape.smooth(ESCAPE_RADIUS, Bailout::Euclidean, 2.0);
+            assert!(smooth >= escape.iterations as f64 && smooth <= escape.iterations as f64 + 1.0);
+        }
+    }
+
+    #[test]
+    fn smooth_ignores_power_zero() {
+        let escape = Escape::new(7, Complex::new(3.0, 0.0));
+        assert_eq!(escape.smooth(ESCAPE_RADIUS, Bailout::Euclidean, 0.0), 7.0);
+        assert_eq!(escape.smooth(ESCAPE_RADIUS, Bailout::Euclidean, 1.0), 7.0);
+    }
+
+    #[test]
+    fn smooth_is_continuous() {
+        // neighbouring points with different iteration counts get close smooth values
+        let mut options = RenderOptions::new(1000, 1);
+        options.radius = 1e10;
+        let mut last: Option<(Escape, f64)> = None;
+        for i in 0..2000 {
+            let escape = mandelbrot(0.26 + i as f64 * 1e-5, 0.0, &options);
+            let smooth = options.smooth_count(&escape);
+            if let Some((last_escape, last_smooth)) = last {
+                if last_escape.iterations != escape.iterations {
+                    assert!((smooth - last_smooth).abs() < 0.1, "{} -> {}", last_smooth, smooth);
+                }
+            }
+            last = Some((escape, smooth));
+        }
+    }
+
+    #[test]
+    fn bailout_norms() {
+        let z = Complex::new(3.0, -4.0);
+        assert_eq!(Bailout::Euclidean.norm(z), 5.0);
+        assert_eq!(Bailout::Manhattan.norm(z), 7.0);
+        assert_eq!(Bailout::Max.norm(z), 4.0);
+        assert_eq!(Bailout::Real.norm(z), 3.0);
+        assert_eq!(Bailout::Imag.norm(z), 4.0);
+        assert!(Bailout::Real.escaped(Complex::new(f64::NAN, 0.0), 2.0));
+        assert_eq!(Bailout::from_name("max"), Some(Bailout::Max));
+        assert_eq!(Bailout::from_name("maximum"), None);
+    }
+
+    #[test]
+    fn bailout_changes_escape() {
+        let mut options = RenderOptions::new(100, 1);
+        // 0.4 + 0.5i leaves the real strip later than the circle of radius 2
+        let euclidean = mandelbrot(0.4, 0.5, &options).iterations;
+        options.bailout = Bailout::Real;
+        let real = mandelbrot(0.4, 0.5, &options).iterations;
+        options.bailout = Bailout::Manhattan;
+        let manhattan = mandelbrot(0.4, 0.5, &options).iterations;
+        assert!(real > euclidean);
+        assert!(manhattan < euclidean);
+        options.radius = 100.0;
+        options.bailout = Bailout::Euclidean;
+        assert!(mandelbrot(0.4, 0.5, &options).iterations > euclidean);
+    }
+
+    #[test]
+    fn periodicity_keeps_escapes() {
+        // early outs may only change how interior points are found, never the escape counts
+        let mut checked = RenderOptions::new(2000, 1);
+        checked.smooth = true;
+        let mut plain = checked.clone();
+        plain.periodicity = false;
+        let julia = Julia::new(-0.8, 0.156);
+        for i in 0..64 {
+            for j in 0..36 {
+                let (x, y) = (-2.1 + i as f64 * 0.045, -1.2 + j as f64 * 0.066);
+                assert_eq!(mandelbrot(x, y, &checked).iterations, mandelbrot(x, y, &plain).iterations);
+                assert_eq!(julia.stable_cords(x, y, &checked).iterations, julia.stable_cords(x, y, &plain).iterations);
+            }
+        }
+    }
+
+    #[test]
+    fn detects_periods() {
+        let options = RenderOptions::new(10000, 1);
+        assert_eq!(mandelbrot(0.1, 0.2, &options).period, Some(1));
+        assert_eq!(mandelbrot(-1.1, 0.05, &options).period, Some(2));
+        // centers of the period 3 and period 4 components
+        assert_eq!(mandelbrot(-0.1225611668766536, 0.7448617666197442, &options).period, Some(3));
+        assert_eq!(mandelbrot(-1.3107026413368328, 0.0, &options).period, Some(4));
+        assert_eq!(mandelbrot(0.5, 0.5, &options).period, None);
+        assert_eq!(Julia::new(-1.0, 0.0).stable_cords(0.1, 0.0, &options).period, Some(2));
+    }
+
+    #[test]
+    fn cardioid_respects_small_radius() {
+        // with a tiny bailout the cardioid isn't inside the escape region anymore
+        let mut options = RenderOptions::new(100, 1);
+        options.radius = 0.5;
+        assert!(mandelbrot(-0.7, 0.0, &options).iterations < 100);
+    }
+
+    #[test]
+    fn subdivision_matches_brute_force() {
+        let mut brute = RenderOptions::new(300, 1);
+        brute.smooth = true;
+        let mut subdivide = brute.clone();
+        subdivide.strategy = Strategy::Subdivide;
+        let julia = Julia::new(-0.8, 0.156);
+        for (x_min, x_max, y_min, y_max) in [(-2.1333, 2.1333, -1.2, 1.2), (-0.8, -0.7, 0.05, 0.15)] {
+            assert!(render_mandelbrot(x_min, x_max, y_min, y_max, 213, 120, &brute, Watch::default()) == render_mandelbrot(x_min, x_max, y_min, y_max, 213, 120, &subdivide, Watch::default()));
+            assert!(render_julia(julia, x_min, x_max, y_min, y_max, 213, 120, &brute, Watch::default()) == render_julia(julia, x_min, x_max, y_min, y_max, 213, 120, &subdivide, Watch::default()));
+        }
+    }
+
+    #[test]
+    fn progressive_reuses_pixels() {
+        let options = RenderOptions::new(200, 1);
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = count.clone();
+        let mut passes = Vec::new();
+        let mut preview = |img: &image::RgbImage| passes.push(img.clone());
+        let img = render(-2.1333, 2.1333, -1.2, 1.2, 161, 90, &options, Watch { preview: Some(&mut preview), ..Watch::default() }, move |x, y, options| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            mandelbrot(x, y, options)
         });
+        // every pixel is computed exactly once over all passes
+        assert_eq!(count.load(Ordering::Relaxed), 161 * 90);
+        assert_eq!(passes.len(), 3);
+        assert!(passes[2] == img);
+        assert!(img == render_mandelbrot(-2.1333, 2.1333, -1.2, 1.2, 161, 90, &options, Watch::default()));
+        // the coarse pass already covers the whole frame
+        assert_eq!(passes[0].get_pixel(3, 3), passes[0].get_pixel(0, 0));
+        assert_eq!(passes[0].get_pixel(160, 89), img.get_pixel(160, 88));
     }
-    
-    for (x, rx) in recievers.into_iter().enumerate() {
-        let colors = rx.recv().unwrap();
-        for (y, c) in colors.into_iter().enumerate() {
-            img.put_pixel(x as u32, y as u32, c);
+
+    #[test]
+    fn histogram_spreads_colors() {
+        let mut options = RenderOptions::new(10_000, 1);
+        let distinct = |img: &image::RgbImage| img.pixels().collect::<std::collections::HashSet<_>>().len();
+        let julia = Julia::new(-0.8, 0.156);
+        let cycled = (render_mandelbrot(-0.75, -0.73, 0.1, 0.11, 160, 90, &options, Watch::default()), render_julia(julia, -2.1333, 2.1333, -1.2, 1.2, 160, 90, &options, Watch::default()));
+        options.coloring = Coloring::Histogram;
+        let mandel = render_mandelbrot(-0.75, -0.73, 0.1, 0.11, 160, 90, &options, Watch::default());
+        let equalized = (mandel.clone(), render_julia(julia, -2.1333, 2.1333, -1.2, 1.2, 160, 90, &options, Watch::default()));
+        assert!(distinct(&equalized.0) > distinct(&cycled.0));
+        assert!(equalized.1 != cycled.1);
+
+        // previews color the whole frame at once too
+        let mut preview = |_: &image::RgbImage| ();
+        let progressive = render_mandelbrot(-0.75, -0.73, 0.1, 0.11, 160, 90, &options, Watch { preview: Some(&mut preview), ..Watch::default() });
+        assert!(progressive == mandel);
+    }
+
+    #[test]
+    fn derivative_matches_finite_difference() {
+        let mut options = RenderOptions::new(100, 1);
+        options.derivative = true;
+        let h = 1e-7;
+        let (c, start) = (Complex::new(0.6, 0.6), Complex::new(0.3, 0.4));
+        let julia = Julia::new(-0.8, 0.156);
+        for power in 1..4 {
+            options.power = power;
+            let escape = mandelbrot(c.real(), c.imag(), &options);
+            let moved = mandelbrot(c.real() + h, c.imag(), &options);
+            assert_eq!(escape.iterations, moved.iterations);
+            let difference = (moved.z - escape.z) * Complex::new(1.0 / h, 0.0);
+            assert!((difference - escape.derivative).dist_from_origin() < 1e-4 * escape.derivative.dist_from_origin());
+
+            let escape = julia.stable(start, &options);
+            let moved = julia.stable(start + Complex::new(h, 0.0), &options);
+            let difference = (moved.z - escape.z) * Complex::new(1.0 / h, 0.0);
+            assert!((difference - escape.derivative).dist_from_origin() < 1e-4 * escape.derivative.dist_from_origin());
         }
     }
-    pool.join();
-    
-    img
-}
\ No newline at end of file
+
+    #[test]
+    fn distance_estimates() {
+        let mut options = RenderOptions::new(1000, 1);
+        options.radius = 1e10;
+        options.coloring = Coloring::Distance;
+        // the set reaches 0.25 on the real axis
+        let distance = mandelbrot(1.0, 0.0, &options).distance().unwrap();
+        assert!(distance > 0.75 / 4.0 && distance < 0.75 * 4.0, "{}", distance);
+        assert_eq!(mandelbrot(1.0, 0.0, &RenderOptions::new(1000, 1)).distance(), None);
+
+        // filaments thinner than a pixel show up next to the interior
+        options.coloring = Coloring::Boundary;
+        let field = mandelbrot_field(-2.1333, 2.1333, -1.2, 1.2, 160, 90, &options);
+        let interior = field.iter().filter(|(_, _, escape)| field.is_interior(escape)).count();
+        let img = colors::color_field(&field, &options);
+        let black = img.pixels().filter(|p| p.0 == [0, 0, 0]).count();
+        assert!(black > interior + 100, "{} black, {} interior", black, interior);
+    }
+
+    #[test]
+    fn interior_colorings() {
+        let mut options = RenderOptions::new(500, 1);
+        options.periodicity = false;
+        let black = colors::color_field(&mandelbrot_field(-2.1333, 2.1333, -1.2, 1.2, 160, 90, &options), &options);
+        for name in ["magnitude", "minimum", "period", "distance", "multiplier"] {
+            options.interior = Interior::from_name(name).unwrap();
+            let field = mandelbrot_field(-2.1333, 2.1333, -1.2, 1.2, 160, 90, &options);
+            let img = colors::color_field(&field, &options);
+            // the exterior keeps its colors, the interior gets some
+            let colored = field.iter().filter(|(x, y, escape)| field.is_interior(escape) && img.get_pixel(*x, *y).0 != [0, 0, 0]).count();
+            assert!(colored > 100, "{}: {} interior pixels colored", name, colored);
+            assert!(field.iter().all(|(x, y, escape)| field.is_interior(escape) || img.get_pixel(x, y) == black.get_pixel(x, y)), "{}", name);
+        }
+        // the period is found without periodicity checking, and subdivision doesn't fill over it
+        options.interior = Interior::Period;
+        assert!(mandelbrot(-1.0, 0.0, &options).period == Some(2));
+        options.interior = Interior::Minimum;
+        options.strategy = Strategy::Subdivide;
+        let subdivided = render_mandelbrot(-2.1333, 2.1333, -1.2, 1.2, 160, 90, &options, Watch::default());
+        options.strategy = Strategy::Brute;
+        assert!(subdivided == render_mandelbrot(-2.1333, 2.1333, -1.2, 1.2, 160, 90, &options, Watch::default()));
+    }
+
+    #[test]
+    fn orbit_traps() {
+        let mut options = RenderOptions::new(100, 1);
+        // 0.5 goes 0.5, 0.75, 1.0625, ... and escapes
+        options.trap = Some(Trap::Point(Complex::new(0.75, 0.0)));
+        let escape = mandelbrot(0.5, 0.0, &options);
+        assert!(escape.iterations < 100);
+        assert_eq!(escape.trap, Some(0.0));
+        // the julia kernel starts from the pixel, -1 -> 0 -> -1 with c = -1
+        options.trap = Some(Trap::Circle(Complex::null(), 0.5));
+        assert_eq!(Julia::new(-1.0, 0.0).stable_cords(-1.0, 0.0, &options).trap, Some(0.5));
+        assert_eq!(mandelbrot(0.5, 0.0, &RenderOptions::new(100, 1)).trap, None);
+
+        // the trap colors the exterior, subdivision has to compute every pixel for it
+        options.trap = Some(Trap::Stalks(Complex::null(), 0.1));
+        let cycled = render_mandelbrot(-2.1333, 2.1333, -1.2, 1.2, 160, 90, &options, Watch::default());
+        options.coloring = Coloring::Trap;
+        let trapped = render_mandelbrot(-2.1333, 2.1333, -1.2, 1.2, 160, 90, &options, Watch::default());
+        assert!(trapped != cycled);
+        options.strategy = Strategy::Subdivide;
+        assert!(trapped == render_mandelbrot(-2.1333, 2.1333, -1.2, 1.2, 160, 90, &options, Watch::default()));
+    }
+
+    #[test]
+    fn orbit_averages() {
+        let mut options = RenderOptions::new(200, 1);
+        options.radius = 1000.0;
+        assert_eq!(mandelbrot(0.6, 0.6, &options).average, None);
+        let mut rendered = Vec::new();
+        for coloring in [Coloring::Stripe, Coloring::Triangle] {
+            options.coloring = coloring;
+            let escape = mandelbrot(0.6, 0.6, &options);
+            let (previous, last) = escape.average.unwrap();
+            assert!((0.0..=1.0).contains(&previous) && (0.0..=1.0).contains(&last));
+            // interior points have no average
+            assert_eq!(mandelbrot(0.0, 0.0, &options).average, None);
+            rendered.push(render_mandelbrot(-2.1333, 2.1333, -1.2, 1.2, 160, 90, &options, Watch::default()));
+        }
+        assert!(rendered[0] != rendered[1]);
+        // subdivision computes every exterior pixel for them
+        options.strategy = Strategy::Subdivide;
+        assert!(rendered[1] == render_mandelbrot(-2.1333, 2.1333, -1.2, 1.2, 160, 90, &options, Watch::default()));
+    }
+
+    #[test]
+    fn formula_variants() {
+        let mut options = RenderOptions::new(200, 1);
+        let julia = Julia::new(-0.8, 0.156);
+        let (c, start) = (Complex::new(-0.2, 0.7), Complex::new(0.1, -0.3));
+        assert_eq!(Plane(Formula::Mandelbrot).stable(c, &options), mandelbrot(c.real(), c.imag(), &options));
+        assert_eq!(Dynamic(Formula::Mandelbrot, julia).stable(start, &options), julia.stable(start, &options));
+        // the tricorn is symmetric about the real axis like the mandelbrot set
+        for i in 0..20 {
+            let (x, y) = (-1.5 + 0.1 * i as f64, 0.05 * i as f64);
+            let tricorn = Plane(Formula::Tricorn);
+            assert_eq!(tricorn.stable_cords(x, y, &options).iterations, tricorn.stable_cords(x, -y, &options).iterations);
+        }
+
+        let formulas = [Formula::Mandelbrot, Formula::BurningShip, Formula::Tricorn, Formula::Celtic, Formula::Perpendicular, Formula::Buffalo];
+        let imgs: Vec<_> = formulas.iter().map(|&formula| {
+            render(-2.1333, 2.1333, -1.2, 1.2, 160, 90, &options, Watch::default(), move |x, y, options: &RenderOptions| Plane(formula).stable_cords(x, y, options))
+        }).collect();
+        for i in 0..imgs.len() {
+            for j in 0..i {
+                assert!(imgs[i] != imgs[j], "{:?} and {:?}", formulas[i], formulas[j]);
+            }
+        }
+        // the folded derivatives still estimate distances
+        options.coloring = Coloring::Distance;
+        options.radius = 1e10;
+        assert!(Plane(Formula::BurningShip).stable_cords(1.0, 0.5, &options).distance().is_some());
+        assert!(Dynamic(Formula::Celtic, julia).stable_cords(1.0, 0.5, &options).distance().is_some());
+    }
+
+    #[test]
+    fn polynomials() {
+        let mut options = RenderOptions::new(200, 1);
+        // the polynomial radius of z^2 + c is larger than 2, past it both escape in the same iteration
+        options.radius = 10.0;
+        options.derivative = true;
+        let square = |a: Complex, parameter| Arc::new(Polynomial::new(vec![a, Complex::null(), Complex::new(1.0, 0.0)], parameter).unwrap());
+        let (c, start) = (Complex::new(0.6, 0.6), Complex::new(0.1, -0.3));
+        let plane = PolyMandelbrot(square(Complex::null(), 0));
+        assert_eq!(plane.stable(c, &options).iterations, mandelbrot(c.real(), c.imag(), &options).iterations);
+        assert_eq!(plane.stable(c, &options).derivative, mandelbrot(c.real(), c.imag(), &options).derivative);
+        assert_eq!(PolyJulia(square(c, 0)).stable(start, &options), c.stable(start, &options));
+
+        // the pixel as the coefficient of z: dz/da_1 picks up z every step
+        let plane = PolyMandelbrot(Arc::new(Polynomial::new(vec![Complex::new(0.3, 0.0), Complex::null(), Complex::new(-0.5, 0.2), Complex::new(1.0, 0.0)], 1).unwrap()));
+        let (a, h) = (Complex::new(1.1, 0.7), 1e-7);
+        let escape = plane.stable(a, &options);
+        let moved = plane.stable(a + Complex::new(h, 0.0), &options);
+        assert!(escape.iterations < 200 && escape.iterations == moved.iterations);
+        let difference = (moved.z - escape.z) * Complex::new(1.0 / h, 0.0);
+        assert!((difference - escape.derivative).dist_from_origin() < 1e-4 * escape.derivative.dist_from_origin());
+
+        // the render threads share it
+        let img = render(-2.1333, 2.1333, -1.2, 1.2, 160, 90, &options, Watch::default(), move |x, y, options: &RenderOptions| plane.stable_cords(x, y, options));
+        assert!(img.pixels().any(|p| p.0 == [0, 0, 0]) && img.pixels().any(|p| p.0 != [0, 0, 0]));
+    }
+
+    #[test]
+    fn root_basins() {
+        let mut options = RenderOptions::new(100, 1);
+        let roots = vec![Complex::new(1.0, 0.0), Complex::new(-0.5, 0.8), Complex::new(-0.5, -0.8), Complex::new(0.0, 0.3)];
+        let finder = Arc::new(RootFinder::from_roots(roots, Method::Newton, Complex::new(1.0, 0.0), false).unwrap());
+        options.newton = Some(finder.clone());
+        options.coloring = Coloring::Roots;
+        let newton = Newton(finder, Complex::null());
+        let stable = move |x, y, options: &RenderOptions| newton.stable_cords(x, y, options);
+        let field = Field::from_columns(escapes(-2.0, 2.0, -1.2, 1.2, 160, 90, &options, Arc::default(), false, stable.clone()), 0.025, &options);
+        // every root has a basin
+        let reached: std::collections::HashSet<_> = field.iter().filter_map(|(_, _, escape)| escape.converged.and_then(|c| c.root)).collect();
+        assert_eq!(reached.len(), 4);
+        let img = colors::color_field(&field, &options);
+        assert!(img == render(-2.0, 2.0, -1.2, 1.2, 160, 90, &options, Watch::default(), stable.clone()));
+        // subdivision doesn't fill over the roots
+        options.strategy = Strategy::Subdivide;
+        assert!(img == render(-2.0, 2.0, -1.2, 1.2, 160, 90, &options, Watch::default(), stable));
+    }
+
+    #[test]
+    fn complex_exponents() {
+        let mut options = RenderOptions::new(200, 1);
+        options.periodicity = false;
+        let square = Plane(Formula::Mandelbrot);
+        let points: Vec<Complex> = (0..40).map(|i| Complex::new(-2.0 + i as f64 * 0.06, 0.4 - i as f64 * 0.02)).collect();
+        let powers: Vec<_> = points.iter().map(|&c| square.stable(c, &options).iterations).collect();
+        // the exponent 2 is the square, taken through the polar form
+        options.exponent = Some(Complex::new(2.0, 0.0));
+        assert_eq!(points.iter().map(|&c| square.stable(c, &options).iterations).collect::<Vec<_>>(), powers);
+        // a real and a complex exponent both give other sets, the smooth counts stay in their bands
+        for exponent in [Complex::new(2.5, 0.0), Complex::new(2.0, 0.3)] {
+            options.exponent = Some(exponent);
+            let escapes: Vec<_> = points.iter().map(|&c| square.stable(c, &options)).collect();
+            assert_ne!(escapes.iter().map(|escape| escape.iterations).collect::<Vec<_>>(), powers);
+            for escape in escapes.iter().filter(|escape| escape.iterations < options.tries) {
+                let smooth = options.smooth_count(escape);
+                assert!(smooth >= escape.iterations as f64 && smooth <= escape.iterations as f64 + 1.0);
+            }
+        }
+        // the morph from z^2 to z^(2 + 0.3i) starts and ends on the exponents
+        let path: Vec<_> = polar::exponent_path((2.0, 0.0), (2.0, 0.3), 5).collect();
+        assert_eq!((path.len(), path[0], path[4]), (5, (2.0, 0.0), (2.0, 0.3)));
+        assert!(path.windows(2).all(|pair| pair[1].1 > pair[0].1));
+        // and turns the short way across the negative axis
+        let turn: Vec<_> = polar::exponent_path((-1.0, 0.1), (-1.0, -0.1), 3).collect();
+        assert!(turn[1].0 < -1.0);
+    }
+
+    #[test]
+    fn cancelled_render_stops() {
+        let mut options = RenderOptions::new(1_000_000, 1);
+        options.periodicity = false;
+        for strategy in [Strategy::Brute, Strategy::Subdivide] {
+            options.strategy = strategy;
+            let control = Arc::new(Control::default());
+            control.cancel();
+            let watch = Watch { control: control.clone(), ..Watch::default() };
+            // would take ages with a million tries for every interior pixel
+            let img = render_mandelbrot(-2.1333, 2.1333, -1.2, 1.2, 160, 90, &options, watch);
+            assert_eq!(control.progress(), 0.0);
+            assert!(img.pixels().all(|p| p.0 == [0, 0, 0]));
+        }
+    }
+
+    #[test]
+    fn reports_progress() {
+        let control = Arc::new(Control::default());
+        let watch = Watch { control: control.clone(), ..Watch::default() };
+        render_mandelbrot(-2.1333, 2.1333, -1.2, 1.2, 160, 90, &RenderOptions::new(100, 1), watch);
+        assert_eq!(control.progress(), 1.0);
+    }
+
+    #[test]
+    fn deep_windows_cancel_the_reference() {
+        let (cx, cy) = (big_from_f64(-1.7685736562, 300), big_from_f64(0.0, 300));
+        let span = FloatExp::new(1e-20);
+        // the reference alone would take ages with a million tries
+        let control = Arc::new(Control::default());
+        control.cancel();
+        let watch = Watch { control: control.clone(), ..Watch::default() };
+        let (img, _) = deep_mandelbrot(&cx, &cy, span, span, 16, 9, &RenderOptions::new(1_000_000, 1), watch);
+        assert!(img.pixels().all(|p| p.0 == [0, 0, 0]));
+        // the orbit is part of the progress, done once the pixels are
+        let control = Arc::new(Control::default());
+        let watch = Watch { control: control.clone(), ..Watch::default() };
+        deep_mandelbrot(&cx, &cy, span, span, 16, 9, &RenderOptions::new(500, 1), watch);
+        assert_eq!(control.progress(), 1.0);
+    }
+
+    #[test]
+    fn field_colors_like_render() {
+        let options = RenderOptions::new(200, 1);
+        let field = mandelbrot_field(-2.1333, 2.1333, -1.2, 1.2, 160, 90, &options);
+        assert!(colors::color_field(&field, &options) == render_mandelbrot(-2.1333, 2.1333, -1.2, 1.2, 160, 90, &options, Watch::default()));
+
+        let mut smooth = options.clone();
+        smooth.smooth = true;
+        let julia = Julia::new(-0.8, 0.156);
+        let field = julia_field(julia, -2.1333, 2.1333, -1.2, 1.2, 160, 90, &options);
+        assert!(colors::color_field(&field, &smooth) == render_julia(julia, -2.1333, 2.1333, -1.2, 1.2, 160, 90, &smooth, Watch::default()));
+    }
+}
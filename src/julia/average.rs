@@ -0,0 +1,112 @@
+use super::complex::Complex;
+
+// statistic of the orbit that gets averaged over the iterations
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Average {
+    // 0.5 + 0.5 sin(density arg z)
+    Stripe(f64),
+    // where |z| lies between the bounds the triangle inequality gives for |z^d + c|
+    Triangle,
+}
+
+impl Average {
+    // value of the statistic for z = zd + c, in [0, 1]
+    fn value(&self, zd: Complex, c: Complex, z: Complex) -> Option<f64> {
+        match *self {
+            Average::Stripe(density) => Some(0.5 + 0.5 * (density * z.imag().atan2(z.real())).sin()),
+            Average::Triangle => {
+                let (zd, c) = (zd.dist_from_origin(), c.dist_from_origin());
+                let (low, high) = ((zd - c).abs(), zd + c);
+                if high > low {
+                    Some((z.dist_from_origin() - low) / (high - low))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+// running average over an orbit
+#[derive(Debug, Copy, Clone)]
+pub struct Accumulator {
+    average: Average,
+    steps: u32,
+    count: u32,
+    sum: f64,
+    // sum before the last value
+    previous: f64,
+}
+
+impl Accumulator {
+    pub fn new(average: Average) -> Self {
+        Accumulator { average, steps: 0, count: 0, sum: 0.0, previous: 0.0 }
+    }
+
+    // z = zd + c is the next iterate, zd the power of the one before.
+    // The first iterate is skipped, for the mandelbrot set it's c itself and the triangle bounds collapse
+    pub fn add(&mut self, zd: Complex, c: Complex, z: Complex) {
+        self.steps += 1;
+        if self.steps == 1 {
+            return;
+        }
+        if let Some(value) = self.average.value(zd, c, z) {
+            self.previous = self.sum;
+            self.sum += value;
+            self.count += 1;
+        }
+    }
+
+    // averages without and with the last value, what the smooth fraction interpolates between
+    pub fn result(&self) -> Option<(f64, f64)> {
+        match self.count {
+            0 => None,
+            1 => Some((self.sum, self.sum)),
+            n => Some((self.previous / (n - 1) as f64, self.sum / n as f64)),
+        }
+    }
+}
+
+// average at the fractional part of the smooth iteration count, continuous across the iteration bands
+pub fn interpolate(average: (f64, f64), fraction: f64) -> f64 {
+    let (previous, last) = average;
+    previous + fraction.clamp(0.0, 1.0) * (last - previous)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Accumulator, Average, interpolate};
+    use super::super::complex::Complex;
+
+    #[test]
+    fn averages_skip_the_first_iterate() {
+        let c = Complex::new(0.0, 1.0);
+        let mut stripes = Accumulator::new(Average::Stripe(1.0));
+        stripes.add(Complex::null(), c, c);
+        assert_eq!(stripes.result(), None);
+        // arg i = pi / 2, arg -1 = pi
+        stripes.add(Complex::new(-1.0, 0.0) - c, c, Complex::new(-1.0, 0.0));
+        stripes.add(Complex::null(), c, c);
+        let (previous, last) = stripes.result().unwrap();
+        assert!((previous - 0.5).abs() < 1e-12);
+        assert!((last - 0.75).abs() < 1e-12);
+        assert_eq!(interpolate((previous, last), 0.5), previous + 0.5 * (last - previous));
+        assert_eq!(interpolate((previous, last), 2.0), last);
+    }
+
+    #[test]
+    fn triangle_stays_within_bounds() {
+        let c = Complex::new(-0.4, 0.6);
+        let mut triangle = Accumulator::new(Average::Triangle);
+        let mut z = Complex::null();
+        for _ in 0..20 {
+            let zd = z * z;
+            z = zd + c;
+            triangle.add(zd, c, z);
+        }
+        let (previous, last) = triangle.result().unwrap();
+        assert!((0.0..=1.0).contains(&previous) && (0.0..=1.0).contains(&last));
+        // the bounds are reached when z^2 and c point the same way
+        assert_eq!(Average::Triangle.value(Complex::new(1.0, 0.0), Complex::new(0.5, 0.0), Complex::new(1.5, 0.0)), Some(1.0));
+    }
+}
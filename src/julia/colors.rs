@@ -1,47 +1,182 @@
-use std::f64::consts::PI;
-use image::{Rgb};
-
-// h: hue as a rotation on the color wheel [0, 2*PI]
-// s: saturation [0,1]
-// v; value (brightness) [0,1]
-fn hsv_to_rgb(h: f64, s: f64, v: f64) -> (u8, u8, u8) {
-    let h = {
-        if h < 2.0 * PI {
-            h
-        } else {
-            h - 2.0 * PI
+use image::{Rgb, RgbImage};
+
+use super::RenderOptions;
+use super::field::Field;
+use super::average;
+
+pub mod palette;
+pub mod gradients;
+pub mod lighting;
+
+pub use palette::{Palette, Interpolation};
+pub use lighting::Lighting;
+
+// how escape values are mapped onto the palette
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Coloring {
+    // one run through the palette every palette.cycle iterations
+    Cycle,
+    // position on the palette by how many pixels of the frame escaped sooner,
+    // one run through the palette spread evenly over the frame
+    Histogram,
+    // by the estimated distance to the set, DISTANCE_SCALE per doubling of the distance in pixels
+    Distance,
+    // white, with everything closer to the set than a pixel black like the interior:
+    // filaments stay visible at any resolution
+    Boundary,
+    // by how close the orbit came to the trap, one run through the palette per unit of distance
+    Trap,
+    // one run through the palette over the stripe average of the orbit, see RenderOptions::stripes
+    Stripe,
+    // one run through the palette over the triangle inequality average of the orbit
+    Triangle,
+    // root finding: the roots spread evenly over the palette, darker the longer the point took to get there
+    Roots,
+}
+
+// palette steps per doubling of the distance
+const DISTANCE_SCALE: f64 = 10.0;
+
+// iterations until a root's color is half as bright
+const ROOT_FADE: f64 = 10.0;
+
+impl Coloring {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "cycle" => Some(Coloring::Cycle),
+            "histogram" => Some(Coloring::Histogram),
+            "distance" => Some(Coloring::Distance),
+            "boundary" => Some(Coloring::Boundary),
+            "trap" => Some(Coloring::Trap),
+            "stripe" => Some(Coloring::Stripe),
+            "triangle" => Some(Coloring::Triangle),
+            "roots" => Some(Coloring::Roots),
+            _ => None,
+        }
+    }
+
+    pub fn needs_derivative(&self) -> bool {
+        matches!(self, Coloring::Distance | Coloring::Boundary)
+    }
+}
+
+// colors a rendered field the way the options ask for (smooth, palette, coloring, interior, lighting)
+pub fn color_field(field: &Field, options: &RenderOptions) -> RgbImage {
+    let mut escape_options = field.options().clone();
+    escape_options.smooth = options.smooth;
+    let values: Vec<Option<f64>> = field.iter()
+        .map(|(_, _, escape)| if field.is_interior(escape) { None } else { Some(escape_options.escape_value(escape)) })
+        .collect();
+    let palette = &options.palette;
+    let colors: Vec<Option<Rgb<u8>>> = match options.coloring {
+        Coloring::Cycle => values.into_iter().map(|value| value.map(|v| palette.color(v))).collect(),
+        Coloring::Histogram => equalize(&values).into_iter()
+            .map(|position| position.map(|t| palette.color(t * palette.cycle)))
+            .collect(),
+        // pixels without a distance keep their escape value
+        Coloring::Distance => field.iter().zip(values)
+            .map(|((_, _, escape), value)| value.map(|v| {
+                let position = field.distance(escape).map_or(v, |d| DISTANCE_SCALE * (1.0 + d).log2());
+                palette.color(position)
+            }))
+            .collect(),
+        Coloring::Boundary => field.iter()
+            .map(|(_, _, escape)| if field.is_interior(escape) || field.distance(escape).is_some_and(|d| d < 1.0) {
+                None
+            } else {
+                Some(Rgb([255, 255, 255]))
+            })
+            .collect(),
+        // pixels the trap never caught keep their escape value
+        Coloring::Trap => field.iter().zip(values)
+            .map(|((_, _, escape), value)| value.map(|v| palette.color(escape.trap.map_or(v, |d| d * palette.cycle))))
+            .collect(),
+        // averages get interpolated with the smooth fraction whether the field is smooth or not,
+        // orbits too short for an average keep their escape value
+        Coloring::Stripe | Coloring::Triangle => field.iter().zip(values)
+            .map(|((_, _, escape), value)| value.map(|v| {
+                let options = field.options();
+                let fraction = options.smooth_count(escape) - escape.iterations as f64;
+                palette.color(escape.average.map_or(v, |average| average::interpolate(average, fraction) * palette.cycle))
+            }))
+            .collect(),
+        // points that didn't reach a root keep their escape value
+        Coloring::Roots => {
+            let roots = field.options().newton.as_ref().map_or(1, |finder| finder.roots().len().max(1));
+            field.iter().zip(values)
+                .map(|((_, _, escape), value)| value.map(|v| match escape.converged.and_then(|converged| converged.root) {
+                    Some(root) => darken(palette.color(palette.cycle * root as f64 / roots as f64), 1.0 / (1.0 + v / ROOT_FADE)),
+                    None => palette.color(v),
+                }))
+                .collect()
         }
     };
-    let h_frac = h / (PI / 3.0);
-    let f = h_frac - h_frac.floor();
-    let p = v * (1.0-s);
-    let q = v * (1.0 - s * f);
-    let t = v* (1.0 - s * (1.0 - f));
-    let (rf, gf,bf) = match h_frac.floor() {
-        x if x == 0.0 => (v, t, p),
-        x if x == 1.0 => (q, v, p),
-        x if x == 2.0 => (p, v, t),
-        x if x == 3.0 => (p, q, v),
-        x if x == 4.0 => (t, p, v),
-        x if x == 5.0 => (v, p, q),
-        x if x == 6.0 => (v, t, p),
-        _ => panic!("got illegal h_frac value in hsv_to_rgb: {}", h_frac)
-    };
-    let (r,g,b) = ((rf * 256.0).floor() as u8, (gf * 256.0).floor() as u8, (bf * 256.0).floor() as u8,);
-    return (r,g,b)
+
+    let mut img = RgbImage::new(field.width(), field.height());
+    for ((x, y, escape), color) in field.iter().zip(colors) {
+        let color = if field.is_interior(escape) {
+            options.interior.position(escape.z, escape.interior, escape.period, field.pixel())
+                .map(|t| palette.color(t * palette.cycle))
+        } else {
+            color
+        };
+        if let Some(color) = color {
+            img.put_pixel(x, y, color);
+        }
+    }
+    if let Some(lighting) = &options.lighting {
+        lighting::shade(field, &mut img, lighting);
+    }
+    img
 }
 
-// color function
-pub fn ratio_to_color(ratio: f64) -> Rgb<u8> {
-    let offset = 1.8 / 3.0;
-    let h = (ratio + offset) * 2.0 * PI;
-    let (r,g,b) = hsv_to_rgb(h, 0.6, 1.0);
-    Rgb([r, g, b])
+fn darken(color: Rgb<u8>, factor: f64) -> Rgb<u8> {
+    let channel = |c: u8| (c as f64 * factor).round() as u8;
+    Rgb([channel(color[0]), channel(color[1]), channel(color[2])])
 }
 
-const STD_DEPTH: u32 = 100;
+// replaces every value by the fraction of values below it, in [0, 1).
+// Values between two whole numbers get interpolated, so smooth values stay smooth.
+// Sorts the whole parts instead of binning them, the counts can go up to any number of tries
+fn equalize(values: &[Option<f64>]) -> Vec<Option<f64>> {
+    let mut floors: Vec<f64> = values.iter().flatten().filter(|v| v.is_finite()).map(|v| v.max(0.0).floor()).collect();
+    floors.sort_by(f64::total_cmp);
+    let total = floors.len().max(1) as f64;
+    // fraction of values below k
+    let cdf = |k: f64| floors.partition_point(|&floor| floor < k) as f64 / total;
+    values.iter().map(|value| value.map(|v| {
+        // the start of the palette, like Palette::sample
+        if !v.is_finite() {
+            return 0.0;
+        }
+        let v = v.max(0.0);
+        let k = v.floor();
+        let below = cdf(k);
+        below + (v - k) * (cdf(k + 1.0) - below)
+    })).collect()
+}
 
-pub fn color_builder(i: u32) -> Rgb<u8> {
-    let ratio = (i % STD_DEPTH) as f64 / STD_DEPTH as f64;
-    ratio_to_color(ratio)
-}
\ No newline at end of file
+#[cfg(test)]
+mod test {
+    use super::equalize;
+
+    #[test]
+    fn equalized_values_spread_evenly() {
+        // most pixels escape early, a few take very long
+        let mut values: Vec<Option<f64>> = (0..1000).map(|i| Some((i % 10) as f64)).collect();
+        values.extend((0..10).map(|i| Some(9000.0 + i as f64)));
+        values.push(None);
+        let equalized = equalize(&values);
+        assert_eq!(equalized[1010], None);
+        let positions: Vec<f64> = equalized.iter().flatten().copied().collect();
+        assert!(positions.iter().all(|t| (0.0..1.0).contains(t)));
+        // the ten early counts take up almost all of the palette instead of a tenth of a cycle
+        assert_eq!(positions[0], 0.0);
+        assert_eq!(positions[9], 900.0 / 1010.0);
+        assert!(positions[1009] > 0.99);
+        // fractional values land between their neighbours
+        assert_eq!(equalize(&[Some(0.0), Some(1.0), Some(0.5)]), vec![Some(0.0), Some(2.0 / 3.0), Some(1.0 / 3.0)]);
+        // counts near u32::MAX don't need a bin for every count below them
+        assert_eq!(equalize(&[Some(4e9), Some(1.0), Some(f64::INFINITY)]), vec![Some(0.5), Some(0.0), Some(0.0)]);
+    }
+}
@@ -0,0 +1,384 @@
+use std::f64::consts::PI;
+#[cfg(test)]
+use std::fmt::Write;
+use std::path::Path;
+
+use super::palette::{Palette, PaletteError, Stop, Interpolation, hsv_to_rgb, rgb_to_hsv};
+
+// gimp segments that don't blend linearly in rgb get sampled into this many pieces
+const SEGMENT_SAMPLES: usize = 16;
+
+// ultrafractal gradients have 400 slots
+const UGR_SLOTS: f64 = 400.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GradientError {
+    Io(String),
+    UnknownFormat(String),
+    // line number starting at 1 and what was wrong with it
+    Syntax(usize, String),
+    Palette(PaletteError),
+}
+
+impl From<PaletteError> for GradientError {
+    fn from(e: PaletteError) -> Self {
+        GradientError::Palette(e)
+    }
+}
+
+fn syntax(line: usize, message: &str) -> GradientError {
+    GradientError::Syntax(line + 1, String::from(message))
+}
+
+/// Reads a .map, .ggr or .ugr file, named after its title or the file name.
+/// Only .ugr files can hold more than one gradient.
+pub fn load(path: &Path) -> Result<Vec<(String, Palette)>, GradientError> {
+    let extension = path.extension().and_then(|ext| ext.to_str());
+    if !matches!(extension, Some("map" | "ggr" | "ugr")) {
+        return Err(GradientError::UnknownFormat(path.display().to_string()));
+    }
+    let text = std::fs::read_to_string(path).map_err(|e| GradientError::Io(e.to_string()))?;
+    let stem = path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+    match extension {
+        Some("map") => Ok(vec![(stem, parse_map(&text)?)]),
+        Some("ggr") => {
+            let (name, palette) = parse_ggr(&text)?;
+            Ok(vec![(if name.is_empty() { stem } else { name }, palette)])
+        }
+        _ => parse_ugr(&text),
+    }
+}
+
+// stops like a file lists them, but without repeating a color at the same position
+fn push(stops: &mut Vec<Stop>, stop: Stop) {
+    if stops.last() != Some(&stop) {
+        stops.push(stop);
+    }
+}
+
+fn channel(text: &str, line: usize) -> Result<u8, GradientError> {
+    text.parse().map_err(|_| syntax(line, "color channels have to be integers from 0 to 255"))
+}
+
+/// Fractint .map: one "r g b" line per color index, anything after the third number is a comment.
+/// Every index gets one iteration, like fractint colors.
+pub fn parse_map(text: &str) -> Result<Palette, GradientError> {
+    let mut colors = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let fields: Vec<&str> = line.split_whitespace().take(3).collect();
+        if fields.is_empty() {
+            continue;
+        }
+        if fields.len() < 3 {
+            return Err(syntax(i, "expected three color channels"));
+        }
+        colors.push([channel(fields[0], i)?, channel(fields[1], i)?, channel(fields[2], i)?]);
+    }
+    let count = colors.len() as f64;
+    let stops = colors.iter().enumerate().map(|(i, c)| Stop::from_rgb(i as f64 / count, *c)).collect();
+    let mut palette = Palette::new(stops, Interpolation::Rgb)?;
+    palette.cycle = count;
+    Ok(palette)
+}
+
+// 256 colors sampled evenly over the gradient. Only imports are exposed, the writers are there for the round trips
+#[cfg(test)]
+pub fn write_map(palette: &Palette) -> String {
+    let mut text = String::new();
+    for i in 0..256 {
+        let c = quantize(palette.sample(i as f64 / 256.0));
+        writeln!(text, "{} {} {}", c[0], c[1], c[2]).unwrap();
+    }
+    text
+}
+
+/// GIMP .ggr: segments with their own midpoint, blending function and rgb or hsv coloring.
+/// Linear rgb segments become plain stops, the others get sampled. Alpha is dropped.
+pub fn parse_ggr(text: &str) -> Result<(String, Palette), GradientError> {
+    let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+    match lines.next() {
+        Some((_, line)) if line.trim() == "GIMP Gradient" => (),
+        _ => return Err(syntax(0, "missing GIMP Gradient header")),
+    }
+    let mut name = String::new();
+    let mut next = lines.next();
+    if let Some((_, line)) = next {
+        if let Some(rest) = line.trim().strip_prefix("Name:") {
+            name = String::from(rest.trim());
+            next = lines.next();
+        }
+    }
+    let (i, line) = next.ok_or_else(|| syntax(1, "missing segment count"))?;
+    let count: usize = line.trim().parse().map_err(|_| syntax(i, "segment count is not a number"))?;
+
+    let mut stops = Vec::new();
+    for _ in 0..count {
+        let (i, line) = lines.next().ok_or_else(|| syntax(i, "fewer segments than announced"))?;
+        let fields = line.split_whitespace()
+            .map(|field| field.parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|_| syntax(i, "segment fields have to be numbers"))?;
+        if fields.len() < 13 {
+            return Err(syntax(i, "segments have at least 13 fields"));
+        }
+        let segment = Segment {
+            left: fields[0],
+            middle: fields[1],
+            right: fields[2],
+            left_color: [fields[3], fields[4], fields[5]],
+            right_color: [fields[7], fields[8], fields[9]],
+            blending: fields[11] as u32,
+            coloring: fields[12] as u32,
+        };
+        segment.stops(&mut stops, i)?;
+    }
+    Ok((name, Palette::new(stops, Interpolation::Rgb)?))
+}
+
+// linear rgb segments between the stops, stops only cover part of [0, 1] get the wrap around added
+#[cfg(test)]
+pub fn write_ggr(name: &str, palette: &Palette) -> String {
+    let mut stops = palette.stops().to_vec();
+    if stops[0].position > 0.0 {
+        stops.insert(0, Stop::new(0.0, palette.sample(0.0)));
+    }
+    if stops[stops.len() - 1].position < 1.0 {
+        stops.push(Stop::new(1.0, palette.sample(1.0)));
+    }
+    let segments: Vec<(&Stop, &Stop)> = stops.iter().zip(stops.iter().skip(1))
+        .filter(|(a, b)| b.position > a.position)
+        .collect();
+
+    let mut text = format!("GIMP Gradient\nName: {}\n{}\n", name, segments.len());
+    for (a, b) in segments {
+        let (l, r) = (a.color, b.color);
+        writeln!(text, "{} {} {} {} {} {} 1 {} {} {} 1 0 0", a.position, (a.position + b.position) / 2.0, b.position,
+            l[0], l[1], l[2], r[0], r[1], r[2]).unwrap();
+    }
+    text
+}
+
+struct Segment {
+    left: f64,
+    middle: f64,
+    right: f64,
+    left_color: [f64; 3],
+    right_color: [f64; 3],
+    // 0 linear, 1 curved, 2 sine, 3 sphere increasing, 4 sphere decreasing, 5 step
+    blending: u32,
+    // 0 rgb, 1 hsv counter clockwise, 2 hsv clockwise
+    coloring: u32,
+}
+
+impl Segment {
+    fn stops(&self, stops: &mut Vec<Stop>, line: usize) -> Result<(), GradientError> {
+        if self.blending > 5 || self.coloring > 2 {
+            return Err(syntax(line, "unknown blending or coloring type"));
+        }
+        let width = self.right - self.left;
+        let middle = if width > 0.0 { (self.middle - self.left) / width } else { 0.5 };
+        push(stops, Stop::new(self.left, self.left_color));
+        if self.blending == 5 {
+            push(stops, Stop::new(self.middle, self.left_color));
+            push(stops, Stop::new(self.middle, self.right_color));
+        } else if self.blending == 0 && self.coloring == 0 {
+            // linear blending is linear on both sides of the midpoint
+            if (middle - 0.5).abs() > 1e-9 {
+                push(stops, Stop::new(self.middle, self.color(0.5)));
+            }
+        } else {
+            for k in 1..SEGMENT_SAMPLES {
+                let pos = k as f64 / SEGMENT_SAMPLES as f64;
+                push(stops, Stop::new(self.left + width * pos, self.color(self.factor(pos, middle))));
+            }
+        }
+        push(stops, Stop::new(self.right, self.right_color));
+        Ok(())
+    }
+
+    // how far to the right color at pos, both pos and middle relative to the segment
+    fn factor(&self, pos: f64, middle: f64) -> f64 {
+        let linear = if pos <= middle {
+            if middle < 1e-10 { 0.0 } else { 0.5 * pos / middle }
+        } else if 1.0 - middle < 1e-10 {
+            1.0
+        } else {
+            0.5 + 0.5 * (pos - middle) / (1.0 - middle)
+        };
+        match self.blending {
+            1 => pos.powf(0.5f64.ln() / middle.max(1e-10).ln()),
+            2 => ((-PI / 2.0 + PI * linear).sin() + 1.0) / 2.0,
+            3 => (1.0 - (linear - 1.0) * (linear - 1.0)).sqrt(),
+            4 => 1.0 - (1.0 - linear * linear).sqrt(),
+            _ => linear,
+        }
+    }
+
+    fn color(&self, f: f64) -> [f64; 3] {
+        let (a, b) = (self.left_color, self.right_color);
+        if self.coloring == 0 {
+            return [a[0] + (b[0] - a[0]) * f, a[1] + (b[1] - a[1]) * f, a[2] + (b[2] - a[2]) * f];
+        }
+        let (a, b) = (rgb_to_hsv(a), rgb_to_hsv(b));
+        // the hue goes the long way around if the direction asks for it
+        let dh = if self.coloring == 1 {
+            (b[0] - a[0]).rem_euclid(1.0)
+        } else {
+            -(a[0] - b[0]).rem_euclid(1.0)
+        };
+        hsv_to_rgb([(a[0] + dh * f).rem_euclid(1.0), a[1] + (b[1] - a[1]) * f, a[2] + (b[2] - a[2]) * f])
+    }
+}
+
+/// UltraFractal .ugr: any number of "name { gradient: ... }" entries,
+/// colors are given as index=i color=c pairs with c packed as blue, green, red bytes.
+pub fn parse_ugr(text: &str) -> Result<Vec<(String, Palette)>, GradientError> {
+    let mut gradients = Vec::new();
+    let mut entry: Option<(String, Vec<Stop>)> = None;
+    let mut in_gradient = false;
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line.strip_suffix('{') {
+            if entry.is_some() {
+                return Err(syntax(i, "entries can't be nested"));
+            }
+            entry = Some((String::from(name.trim()), Vec::new()));
+            in_gradient = false;
+            continue;
+        }
+        let (name, stops) = entry.as_mut().ok_or_else(|| syntax(i, "expected the start of an entry"))?;
+        if line == "}" {
+            let stops = std::mem::take(stops);
+            let name = std::mem::take(name);
+            gradients.push((name, Palette::new(stops, Interpolation::Rgb)?));
+            entry = None;
+        } else if line.ends_with(':') {
+            // opacity and other sections are skipped
+            in_gradient = line == "gradient:";
+        } else if in_gradient {
+            let mut index = None;
+            for (key, value) in attributes(line) {
+                match key {
+                    "title" => *name = String::from(value),
+                    "index" => index = Some(value.parse::<f64>().map_err(|_| syntax(i, "index is not a number"))?),
+                    "color" => {
+                        let index = index.take().ok_or_else(|| syntax(i, "color without index"))?;
+                        let c: u32 = value.parse().map_err(|_| syntax(i, "color is not a number"))?;
+                        let color = [(c & 0xff) as u8, (c >> 8 & 0xff) as u8, (c >> 16 & 0xff) as u8];
+                        stops.push(Stop::from_rgb(index.rem_euclid(UGR_SLOTS) / UGR_SLOTS, color));
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+    if entry.is_some() {
+        return Err(syntax(text.lines().count(), "unclosed entry"));
+    }
+    Ok(gradients)
+}
+
+#[cfg(test)]
+pub fn write_ugr(gradients: &[(String, Palette)]) -> String {
+    let mut text = String::new();
+    for (name, palette) in gradients {
+        writeln!(text, "{} {{\ngradient:\n  title=\"{}\" smooth=no", name, name).unwrap();
+        for stop in palette.stops() {
+            let c = quantize(stop.color);
+            let color = c[0] as u32 | (c[1] as u32) << 8 | (c[2] as u32) << 16;
+            writeln!(text, "  index={} color={}", (stop.position * UGR_SLOTS).round(), color).unwrap();
+        }
+        writeln!(text, "opacity:\n  smooth=no index=0 opacity=255\n}}\n").unwrap();
+    }
+    text
+}
+
+// key=value pairs, values can be quoted to contain spaces
+fn attributes(line: &str) -> Vec<(&str, &str)> {
+    let mut pairs = Vec::new();
+    let mut rest = line.trim_start();
+    while let Some(eq) = rest.find('=') {
+        let key = rest[..eq].trim();
+        let after = &rest[eq + 1..];
+        let (value, next) = if let Some(quoted) = after.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            (&quoted[..end], quoted.get(end + 1..).unwrap_or(""))
+        } else {
+            let end = after.find(char::is_whitespace).unwrap_or(after.len());
+            (&after[..end], &after[end..])
+        };
+        pairs.push((key, value));
+        rest = next.trim_start();
+    }
+    pairs
+}
+
+#[cfg(test)]
+fn quantize(c: [f64; 3]) -> [u8; 3] {
+    let channel = |x: f64| (x.clamp(0.0, 1.0) * 255.0).round() as u8;
+    [channel(c[0]), channel(c[1]), channel(c[2])]
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use super::{parse_map, write_map, parse_ggr, write_ggr, parse_ugr, write_ugr, load, GradientError};
+    use super::super::palette::Interpolation;
+
+    const MAP: &str = include_str!("../../../palettes/volcano.map");
+    const GGR: &str = include_str!("../../../palettes/sunset.ggr");
+    const UGR: &str = include_str!("../../../palettes/collection.ugr");
+
+    #[test]
+    fn map_round_trip() {
+        let palette = parse_map(MAP).unwrap();
+        assert_eq!(palette.stops().len(), 256);
+        assert_eq!(palette.cycle, 256.0);
+        assert_eq!(parse_map(&write_map(&palette)).unwrap(), palette);
+        assert!(matches!(parse_map("1 2\n"), Err(GradientError::Syntax(1, _))));
+        assert!(matches!(parse_map("0 0 0\n1 2 300\n"), Err(GradientError::Syntax(2, _))));
+    }
+
+    #[test]
+    fn ggr_round_trip() {
+        let (name, palette) = parse_ggr(GGR).unwrap();
+        assert_eq!(name, "Sunset");
+        assert_eq!(palette.interpolation, Interpolation::Rgb);
+        // the off center midpoint adds a stop, the sine and hsv segments get sampled
+        assert!(palette.stops().len() > 2 * 16);
+        let (again, copy) = parse_ggr(&write_ggr(&name, &palette)).unwrap();
+        assert_eq!((again, copy), (name, palette));
+        assert!(parse_ggr("GIMP Gradient\n2\n0 0.5 1 0 0 0 1 1 1 1 1 0 0\n").is_err());
+    }
+
+    #[test]
+    fn ggr_linear_midpoint() {
+        let (_, palette) = parse_ggr("GIMP Gradient\nName: Mid\n1\n0 0.25 1 0 0 0 1 1 1 1 1 0 0\n").unwrap();
+        // the midpoint is where the colors are mixed half and half
+        let half = palette.sample(0.25);
+        assert!(half.iter().all(|c| (c - 0.5).abs() < 1e-12));
+    }
+
+    #[test]
+    fn ugr_round_trip() {
+        let gradients = parse_ugr(UGR).unwrap();
+        let names: Vec<&str> = gradients.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["Deep Sea", "Embers"]);
+        assert_eq!(gradients[0].1.stops()[0].color, [0.0, 0.0, 64.0 / 255.0]);
+        assert_eq!(parse_ugr(&write_ugr(&gradients)).unwrap(), gradients);
+        assert!(parse_ugr("open {\ngradient:\n index=0 color=0\n").is_err());
+    }
+
+    #[test]
+    fn loads_by_extension() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("palettes");
+        assert_eq!(load(&dir.join("volcano.map")).unwrap()[0].0, "volcano");
+        assert_eq!(load(&dir.join("sunset.ggr")).unwrap()[0].0, "Sunset");
+        assert_eq!(load(&dir.join("collection.ugr")).unwrap().len(), 2);
+        assert!(matches!(load(&dir.join("missing.map")), Err(GradientError::Io(_))));
+        assert!(matches!(load(&dir.join("volcano.txt")), Err(GradientError::UnknownFormat(_))));
+    }
+}
@@ -0,0 +1,148 @@
+use image::{Rgb, RgbImage};
+
+use super::super::complex::Complex;
+use super::super::field::Field;
+
+// sharpness of the specular highlight
+const SHININESS: f64 = 20.0;
+
+/// Directional light on the field treated as a height map, higher the closer a pixel gets to the set.
+/// Normals come from the derivative if it was tracked, from the smooth iteration counts of the neighbours otherwise.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Lighting {
+    // direction the light comes from in degrees, counter clockwise from the positive real axis
+    pub angle: f64,
+    // degrees above the plane, 90 is straight overhead
+    pub elevation: f64,
+    // share of the color that's lit regardless of the normal, [0, 1]
+    pub ambient: f64,
+    // strength of the highlight, [0, 1]
+    pub specular: f64,
+    // how steep the relief is
+    pub height: f64,
+}
+
+impl Default for Lighting {
+    fn default() -> Self {
+        Lighting { angle: 45.0, elevation: 45.0, ambient: 0.3, specular: 0.3, height: 1.0 }
+    }
+}
+
+impl Lighting {
+    fn direction(&self) -> [f64; 3] {
+        let (angle, elevation) = (self.angle.to_radians(), self.elevation.to_radians());
+        [elevation.cos() * angle.cos(), elevation.cos() * angle.sin(), elevation.sin()]
+    }
+
+    // color lit from the light direction, the viewer looks straight down
+    fn light(&self, color: Rgb<u8>, normal: [f64; 3], light: [f64; 3]) -> Rgb<u8> {
+        let diffuse = dot(normal, light).max(0.0);
+        let half = normalize([light[0], light[1], light[2] + 1.0]);
+        let highlight = self.specular * dot(normal, half).max(0.0).powf(SHININESS) * 255.0;
+        let brightness = self.ambient + (1.0 - self.ambient) * diffuse;
+        let channel = |c: u8| (c as f64 * brightness + highlight).round().clamp(0.0, 255.0) as u8;
+        Rgb([channel(color[0]), channel(color[1]), channel(color[2])])
+    }
+}
+
+// shades the exterior pixels of an image colored from the field, the interior stays as it is
+pub fn shade(field: &Field, img: &mut RgbImage, lighting: &Lighting) {
+    let (width, height) = (field.width(), field.height());
+    let heights: Vec<Option<f64>> = field.iter()
+        .zip(field.smooth())
+        .map(|((_, _, escape), smooth)| if field.is_interior(escape) { None } else { Some((1.0 + smooth).ln()) })
+        .collect();
+    let at = |x: i64, y: i64| {
+        if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+            return None;
+        }
+        heights[(y * width as i64 + x) as usize]
+    };
+    let light = lighting.direction();
+
+    for (x, y, escape) in field.iter() {
+        let here = match at(x as i64, y as i64) {
+            Some(here) => here,
+            None => continue,
+        };
+        let normal = if escape.derivative != Complex::null() {
+            // the potential falls off along z / dz, away from the set
+            let u = escape.z / escape.derivative;
+            let u = u * Complex::new(1.0 / u.dist_from_origin(), 0.0);
+            normalize([u.real() * lighting.height, u.imag() * lighting.height, 1.0])
+        } else {
+            // central differences, one sided next to the interior and the edges
+            let slope = |before: Option<f64>, after: Option<f64>| match (before, after) {
+                (Some(b), Some(a)) => (a - b) / 2.0,
+                (Some(b), None) => here - b,
+                (None, Some(a)) => a - here,
+                (None, None) => 0.0,
+            };
+            let (x, y) = (x as i64, y as i64);
+            let dx = slope(at(x - 1, y), at(x + 1, y));
+            let dy = slope(at(x, y - 1), at(x, y + 1));
+            normalize([-dx * lighting.height, -dy * lighting.height, 1.0])
+        };
+        let color = *img.get_pixel(x, y);
+        img.put_pixel(x, y, lighting.light(color, normal, light));
+    }
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn normalize(v: [f64; 3]) -> [f64; 3] {
+    let length = dot(v, v).sqrt();
+    [v[0] / length, v[1] / length, v[2] / length]
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgb, RgbImage};
+    use super::{Lighting, shade};
+    use super::super::super::{Escape, RenderOptions, complex::Complex};
+    use super::super::super::field::Field;
+
+    fn slope(width: u32, height: u32) -> Field {
+        // iterations grow to the right like the field does towards the set
+        let columns = (0..width).map(|x| vec![Escape::new(x * 10, Complex::null()); height as usize]).collect();
+        Field::from_columns(columns, 0.1, &RenderOptions::new(1000, 1))
+    }
+
+    #[test]
+    fn flat_field_under_overhead_light() {
+        let columns = vec![vec![Escape::new(5, Complex::null()); 4]; 4];
+        let field = Field::from_columns(columns, 0.1, &RenderOptions::new(100, 1));
+        let mut img = RgbImage::from_pixel(4, 4, Rgb([100, 150, 200]));
+        let lighting = Lighting { elevation: 90.0, specular: 0.0, ..Lighting::default() };
+        shade(&field, &mut img, &lighting);
+        assert!(img.pixels().all(|p| *p == Rgb([100, 150, 200])));
+    }
+
+    #[test]
+    fn slopes_facing_the_light_are_brighter() {
+        let field = slope(8, 4);
+        let lit = |angle| {
+            let mut img = RgbImage::from_pixel(8, 4, Rgb([100, 100, 100]));
+            shade(&field, &mut img, &Lighting { angle, specular: 0.0, ..Lighting::default() });
+            img.get_pixel(4, 2)[0]
+        };
+        // the field rises to the right, so its slopes face left
+        assert!(lit(180.0) > lit(0.0));
+        assert!(lit(180.0) > 100 / 2);
+    }
+
+    #[test]
+    fn derivative_gives_the_normal() {
+        let escape = Escape::new(3, Complex::new(10.0, 0.0)).with_derivative(Complex::new(1.0, 0.0));
+        let field = Field::from_columns(vec![vec![escape]], 0.1, &RenderOptions::new(100, 1));
+        let lit = |angle| {
+            let mut img = RgbImage::from_pixel(1, 1, Rgb([100, 100, 100]));
+            shade(&field, &mut img, &Lighting { angle, specular: 0.0, ..Lighting::default() });
+            img.get_pixel(0, 0)[0]
+        };
+        // z / dz points along the positive real axis, away from the set
+        assert!(lit(0.0) > lit(180.0));
+    }
+}
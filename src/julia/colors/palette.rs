@@ -0,0 +1,347 @@
+use image::Rgb;
+
+// how colors between two stops are mixed
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Interpolation {
+    // straight on the srgb values
+    Rgb,
+    // on linear light, keeps the brightness of mixes right
+    LinearLight,
+    // along the shorter way around the hue wheel
+    Hsv,
+    // perceptually even steps
+    Oklab,
+}
+
+impl Interpolation {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rgb" => Some(Interpolation::Rgb),
+            "linear" => Some(Interpolation::LinearLight),
+            "hsv" => Some(Interpolation::Hsv),
+            "oklab" => Some(Interpolation::Oklab),
+            _ => None,
+        }
+    }
+
+    fn mix(&self, a: [f64; 3], b: [f64; 3], t: f64) -> [f64; 3] {
+        match self {
+            Interpolation::Rgb => lerp(a, b, t),
+            Interpolation::LinearLight => to_srgb(lerp(to_linear(a), to_linear(b), t)),
+            Interpolation::Hsv => {
+                let (a, b) = (rgb_to_hsv(a), rgb_to_hsv(b));
+                // hue in turns, wrapped onto the shorter arc
+                let mut dh = b[0] - a[0];
+                if dh > 0.5 {
+                    dh -= 1.0;
+                } else if dh < -0.5 {
+                    dh += 1.0;
+                }
+                let h = (a[0] + dh * t).rem_euclid(1.0);
+                hsv_to_rgb([h, a[1] + (b[1] - a[1]) * t, a[2] + (b[2] - a[2]) * t])
+            }
+            Interpolation::Oklab => from_oklab(lerp(to_oklab(a), to_oklab(b), t)),
+        }
+    }
+}
+
+// color at a position of the gradient, colors are srgb in [0, 1]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Stop {
+    pub position: f64,
+    pub color: [f64; 3],
+}
+
+impl Stop {
+    pub fn new(position: f64, color: [f64; 3]) -> Self {
+        Stop { position, color }
+    }
+
+    // color given in 0-255 channels
+    pub fn from_rgb(position: f64, color: [u8; 3]) -> Self {
+        Stop::new(position, [color[0] as f64 / 255.0, color[1] as f64 / 255.0, color[2] as f64 / 255.0])
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PaletteError {
+    NoStops,
+    // positions have to be in [0, 1]
+    OutOfRange(f64),
+}
+
+/// Cyclic gradient: escape values run through it every `cycle` iterations,
+/// after the last stop it blends back into the first one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    // sorted by position
+    stops: Vec<Stop>,
+    pub interpolation: Interpolation,
+    // escape values per run through the gradient
+    pub cycle: f64,
+    // fraction of the gradient the colors are shifted by
+    pub offset: f64,
+    pub reverse: bool,
+}
+
+pub const DEFAULT_CYCLE: f64 = 100.0;
+
+impl Palette {
+    pub fn new(stops: Vec<Stop>, interpolation: Interpolation) -> Result<Self, PaletteError> {
+        if stops.is_empty() {
+            return Err(PaletteError::NoStops);
+        }
+        if let Some(stop) = stops.iter().find(|stop| !(0.0..=1.0).contains(&stop.position)) {
+            return Err(PaletteError::OutOfRange(stop.position));
+        }
+        let mut stops = stops;
+        stops.sort_by(|a, b| a.position.partial_cmp(&b.position).unwrap());
+        Ok(Palette {
+            stops,
+            interpolation,
+            cycle: DEFAULT_CYCLE,
+            offset: 0.0,
+            reverse: false,
+        })
+    }
+
+    // stops spread evenly from 0 to 1
+    fn even(colors: &[[u8; 3]], interpolation: Interpolation) -> Self {
+        let last = (colors.len() - 1).max(1) as f64;
+        let stops = colors.iter().enumerate().map(|(i, c)| Stop::from_rgb(i as f64 / last, *c)).collect();
+        Palette::new(stops, interpolation).expect("built in palettes are valid")
+    }
+
+    pub fn names() -> &'static [&'static str] {
+        &["classic", "fire", "ocean", "ultra", "grayscale", "rainbow"]
+    }
+
+    pub fn named(name: &str) -> Option<Self> {
+        let palette = match name {
+            // the original color wheel: hsv with saturation 0.6, starting at 1.8/3 of a turn
+            "classic" => {
+                let stops = (0..6).map(|i| {
+                    let t = i as f64 / 6.0;
+                    Stop::new(t, hsv_to_rgb([(t + 0.6).rem_euclid(1.0), 0.6, 1.0]))
+                }).collect();
+                Palette::new(stops, Interpolation::Hsv).unwrap()
+            }
+            "fire" => Palette::even(&[[0, 0, 0], [128, 0, 0], [230, 60, 0], [255, 180, 0], [255, 255, 200], [255, 180, 0], [128, 0, 0]], Interpolation::LinearLight),
+            "ocean" => Palette::even(&[[0, 7, 40], [0, 60, 130], [0, 170, 200], [220, 250, 255], [0, 170, 200], [0, 60, 130]], Interpolation::Oklab),
+            "ultra" => Palette::new(vec![
+                Stop::from_rgb(0.0, [0, 7, 100]),
+                Stop::from_rgb(0.16, [32, 107, 203]),
+                Stop::from_rgb(0.42, [237, 255, 255]),
+                Stop::from_rgb(0.6425, [255, 170, 0]),
+                Stop::from_rgb(0.8575, [0, 2, 0]),
+            ], Interpolation::Oklab).unwrap(),
+            "grayscale" => Palette::even(&[[0, 0, 0], [255, 255, 255]], Interpolation::LinearLight),
+            "rainbow" => Palette::even(&[[255, 0, 0], [255, 255, 0], [0, 255, 0], [0, 255, 255], [0, 0, 255], [255, 0, 255]], Interpolation::Hsv),
+            _ => return None,
+        };
+        Some(palette)
+    }
+
+    #[cfg(test)]
+    pub fn stops(&self) -> &[Stop] {
+        &self.stops
+    }
+
+    // color for an escape value, fractional values blend between the stops
+    pub fn color(&self, value: f64) -> Rgb<u8> {
+        let t = (value / self.cycle + self.offset).rem_euclid(1.0);
+        let t = if self.reverse { 1.0 - t } else { t };
+        quantize(self.sample(t))
+    }
+
+    // srgb color at t in [0, 1] of the gradient, the first stop's color if t isn't finite
+    pub fn sample(&self, t: f64) -> [f64; 3] {
+        let stops = &self.stops;
+        let first = stops[0];
+        let last = stops[stops.len() - 1];
+        if !t.is_finite() {
+            return first.color;
+        }
+        // between the last and the first stop the gradient wraps around
+        let (a, b, a_pos, b_pos) = if t < first.position {
+            (last, first, last.position - 1.0, first.position)
+        } else if t >= last.position {
+            (last, first, last.position, first.position + 1.0)
+        } else {
+            let i = stops.iter().rposition(|stop| stop.position <= t).unwrap();
+            (stops[i], stops[i + 1], stops[i].position, stops[i + 1].position)
+        };
+        let width = b_pos - a_pos;
+        if width <= 0.0 {
+            return a.color;
+        }
+        self.interpolation.mix(a.color, b.color, (t - a_pos) / width)
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::named("classic").unwrap()
+    }
+}
+
+fn quantize(c: [f64; 3]) -> Rgb<u8> {
+    let channel = |x: f64| (x.clamp(0.0, 1.0) * 255.0).round() as u8;
+    Rgb([channel(c[0]), channel(c[1]), channel(c[2])])
+}
+
+fn lerp(a: [f64; 3], b: [f64; 3], t: f64) -> [f64; 3] {
+    [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t, a[2] + (b[2] - a[2]) * t]
+}
+
+fn to_linear(c: [f64; 3]) -> [f64; 3] {
+    let channel = |x: f64| if x <= 0.04045 { x / 12.92 } else { ((x + 0.055) / 1.055).powf(2.4) };
+    [channel(c[0]), channel(c[1]), channel(c[2])]
+}
+
+fn to_srgb(c: [f64; 3]) -> [f64; 3] {
+    let channel = |x: f64| if x <= 0.0031308 { 12.92 * x } else { 1.055 * x.powf(1.0 / 2.4) - 0.055 };
+    [channel(c[0]), channel(c[1]), channel(c[2])]
+}
+
+// hue in turns [0, 1), saturation and value in [0, 1]
+pub fn hsv_to_rgb(hsv: [f64; 3]) -> [f64; 3] {
+    let [h, s, v] = hsv;
+    let h6 = h.rem_euclid(1.0) * 6.0;
+    let f = h6 - h6.floor();
+    let p = v * (1.0 - s);
+    let q = v * (1.0 - s * f);
+    let t = v * (1.0 - s * (1.0 - f));
+    match h6.floor() as u32 {
+        0 => [v, t, p],
+        1 => [q, v, p],
+        2 => [p, v, t],
+        3 => [p, q, v],
+        4 => [t, p, v],
+        _ => [v, p, q],
+    }
+}
+
+pub fn rgb_to_hsv(c: [f64; 3]) -> [f64; 3] {
+    let [r, g, b] = c;
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let d = max - min;
+    let h = if d == 0.0 {
+        0.0
+    } else if max == r {
+        ((g - b) / d).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / d + 2.0
+    } else {
+        (r - g) / d + 4.0
+    };
+    let s = if max == 0.0 { 0.0 } else { d / max };
+    [h / 6.0, s, max]
+}
+
+// https://bottosson.github.io/posts/oklab/
+fn to_oklab(c: [f64; 3]) -> [f64; 3] {
+    let [r, g, b] = to_linear(c);
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+    [
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    ]
+}
+
+fn from_oklab(c: [f64; 3]) -> [f64; 3] {
+    let [l, a, b] = c;
+    let l_ = (l + 0.3963377774 * a + 0.2158037573 * b).powi(3);
+    let m_ = (l - 0.1055613458 * a - 0.0638541728 * b).powi(3);
+    let s_ = (l - 0.0894841775 * a - 1.2914855480 * b).powi(3);
+    to_srgb([
+        4.0767416621 * l_ - 3.3077115913 * m_ + 0.2309699292 * s_,
+        -1.2684380046 * l_ + 2.6097574011 * m_ - 0.3413193965 * s_,
+        -0.0041960863 * l_ - 0.7034186147 * m_ + 1.7076147010 * s_,
+    ])
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Palette, Stop, Interpolation, PaletteError, to_oklab, from_oklab, rgb_to_hsv, hsv_to_rgb};
+    use image::Rgb;
+
+    fn close(a: [f64; 3], b: [f64; 3]) -> bool {
+        a.iter().zip(b.iter()).all(|(x, y)| (x - y).abs() < 1e-6)
+    }
+
+    #[test]
+    fn conversions_round_trip() {
+        for c in [[0.2, 0.5, 0.9], [1.0, 0.0, 0.0], [0.0, 0.0, 0.0], [0.7, 0.7, 0.1]] {
+            assert!(close(from_oklab(to_oklab(c)), c));
+            assert!(close(hsv_to_rgb(rgb_to_hsv(c)), c));
+        }
+    }
+
+    #[test]
+    fn stops_are_hit_exactly() {
+        for interpolation in [Interpolation::Rgb, Interpolation::LinearLight, Interpolation::Hsv, Interpolation::Oklab] {
+            let palette = Palette::new(vec![
+                Stop::from_rgb(0.0, [255, 0, 0]),
+                Stop::from_rgb(0.5, [0, 0, 255]),
+            ], interpolation).unwrap();
+            assert_eq!(palette.color(0.0), Rgb([255, 0, 0]));
+            assert_eq!(palette.color(50.0), Rgb([0, 0, 255]));
+            // cyclic: a full cycle later it's the same color again
+            assert_eq!(palette.color(125.0), palette.color(25.0));
+        }
+    }
+
+    #[test]
+    fn interpolation_modes_differ() {
+        let stops = vec![Stop::from_rgb(0.0, [255, 0, 0]), Stop::from_rgb(0.5, [0, 255, 0])];
+        let mid = |interpolation| Palette::new(stops.clone(), interpolation).unwrap().color(25.0);
+        assert_eq!(mid(Interpolation::Rgb), Rgb([128, 128, 0]));
+        assert_eq!(mid(Interpolation::Hsv), Rgb([255, 255, 0]));
+        assert_eq!(mid(Interpolation::LinearLight), Rgb([188, 188, 0]));
+        assert!(mid(Interpolation::Oklab) != mid(Interpolation::Rgb));
+    }
+
+    #[test]
+    fn offset_and_reverse() {
+        let mut palette = Palette::named("fire").unwrap();
+        let plain = palette.color(30.0);
+        palette.offset = 0.1;
+        assert_eq!(palette.color(20.0), plain);
+        palette.offset = 0.0;
+        palette.reverse = true;
+        assert_eq!(palette.color(70.0), plain);
+        palette.reverse = false;
+        palette.cycle = 50.0;
+        assert_eq!(palette.color(15.0), plain);
+    }
+
+    #[test]
+    fn non_finite_values() {
+        let mut palette = Palette::named("fire").unwrap();
+        let first = palette.stops[0].color;
+        assert_eq!(palette.sample(f64::NAN), first);
+        assert_eq!(palette.sample(f64::INFINITY), first);
+        palette.offset = f64::NAN;
+        palette.color(10.0);
+        palette.offset = 0.0;
+        palette.color(f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn named_palettes() {
+        for name in Palette::names() {
+            let palette = Palette::named(name).unwrap();
+            for i in 0..200 {
+                palette.color(i as f64 * 0.73);
+            }
+        }
+        assert!(Palette::named("nope").is_none());
+        assert_eq!(Palette::new(vec![], Interpolation::Rgb), Err(PaletteError::NoStops));
+        assert_eq!(Palette::new(vec![Stop::new(1.5, [0.0; 3])], Interpolation::Rgb), Err(PaletteError::OutOfRange(1.5)));
+    }
+}
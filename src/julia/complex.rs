@@ -1,3 +1,4 @@
+use std::f64::consts::PI;
 use std::ops::{Add, AddAssign, Sub, SubAssign, Mul, Div, Neg};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -9,8 +10,8 @@ pub struct Complex {
 impl Complex {
     pub fn new(real: f64, imag: f64) -> Self {
         Self {
-            real: real,
-            imag: imag,
+            real,
+            imag,
         }
     }
 
@@ -21,6 +22,14 @@ impl Complex {
         }
     }
 
+    pub fn real(&self) -> f64 {
+        self.real
+    }
+
+    pub fn imag(&self) -> f64 {
+        self.imag
+    }
+
     pub fn con(&self) -> Self {
         Self {
             real: self.real,
@@ -33,6 +42,23 @@ impl Complex {
         x.sqrt()
     }
     
+    // angle in (-pi, pi], both signs of a zero imaginary part land on the upper side of the branch cut
+    pub fn arg(&self) -> f64 {
+        let a = self.imag.atan2(self.real);
+        if a == -PI { PI } else { a }
+    }
+
+    // principal value exp(exponent ln z), 0 stays 0
+    pub fn powc(self, exponent: Complex) -> Self {
+        if self == Complex::null() {
+            return self;
+        }
+        let (ln_r, a) = (self.dist_from_origin().ln(), self.arg());
+        let modulus = (exponent.real * ln_r - exponent.imag * a).exp();
+        let angle = exponent.imag * ln_r + exponent.real * a;
+        Complex::new(modulus * angle.cos(), modulus * angle.sin())
+    }
+
     pub fn powi(self, exponent: u32) -> Self {
         if exponent == 0 {
             Complex::new(1.0, 0.0)
@@ -107,10 +133,13 @@ impl Div for Complex {
                 imag: self.imag / other.real,
             }
         } else {
-            let con = other.con();
-            let num = self * con;
-            let denom = other * con;
-            num / denom
+            // other * con isn't exactly real with mul_add, dividing by it again would never end
+            let num = self * other.con();
+            let denom = other.real * other.real + other.imag * other.imag;
+            Self {
+                real: num.real / denom,
+                imag: num.imag / denom,
+            }
         }
     }
 }
@@ -194,6 +223,10 @@ mod test {
         assert_eq!(d / b, Complex::new(-4.0,5.0));
 
         assert_eq!(c / d, Complex::new(-22.0 / 41.0, -7.0 / 41.0));
+
+        // the product with the conjugate doesn't come out exactly real for these
+        let e = Complex::new(0.1, 0.7) / Complex::new(-1.3, 0.37);
+        assert!((e * Complex::new(-1.3, 0.37) - Complex::new(0.1, 0.7)).dist_from_origin() < 1e-15);
     }
 
     #[test]
@@ -209,6 +242,21 @@ mod test {
         assert_eq!(d.dist_from_origin(), 41f64.sqrt());
     }
 
+    #[test]
+    fn complex_powers() {
+        let z = Complex::new(-3.0, 4.0);
+        let close = |a: Complex, b: Complex| (a - b).dist_from_origin() < 1e-12 * b.dist_from_origin().max(1.0);
+        assert!(close(z.powc(Complex::new(2.0, 0.0)), z * z));
+        assert!(close(z.powc(Complex::new(3.0, 0.0)), z.powi(2)));
+        assert!(close(Complex::new(4.0, 0.0).powc(Complex::new(0.5, 0.0)), Complex::new(2.0, 0.0)));
+        // i^i = e^(-pi / 2)
+        assert!(close(Complex::new(0.0, 1.0).powc(Complex::new(0.0, 1.0)), Complex::new((-std::f64::consts::FRAC_PI_2).exp(), 0.0)));
+        // the negative real axis belongs to the upper half, whichever zero it has
+        assert_eq!(Complex::new(-1.0, -0.0).arg(), std::f64::consts::PI);
+        assert!(close(Complex::new(-4.0, -0.0).powc(Complex::new(0.5, 0.0)), Complex::new(0.0, 2.0)));
+        assert_eq!(Complex::null().powc(Complex::new(2.5, 0.3)), Complex::null());
+    }
+
     #[test]
     fn neg() {
         let num = Complex::new(2.1, -7.5);
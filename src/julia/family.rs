@@ -0,0 +1,229 @@
+use super::complex::Complex;
+use super::formula::Exponent;
+use super::{orbit, Escape, Fractal, RenderOptions, Step};
+
+// bailout the magnet maps are usually drawn with, their orbits can go past the usual radius and come back
+const MAGNET_RADIUS: f64 = 100.0;
+
+// escape time maps that aren't f(z) + c
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Family {
+    // z^d + c + p z_prev with p from RenderOptions::phoenix, the power like the formulas take it
+    Phoenix,
+    // ((z^2 + c - 1) / (2z + c - 2))^2, superattracting at 1
+    MagnetI,
+    // ((z^3 + 3(c - 1)z + (c - 1)(c - 2)) / (3z^2 + 3(c - 2)z + (c - 1)(c - 2) + 1))^2, also converges to 1
+    MagnetII,
+    // the logistic map c z (1 - z)
+    Lambda,
+}
+
+impl Family {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "phoenix" => Some(Family::Phoenix),
+            "magnet1" | "magnet_i" => Some(Family::MagnetI),
+            "magnet2" | "magnet_ii" => Some(Family::MagnetII),
+            "lambda" | "logistic" => Some(Family::Lambda),
+            _ => None,
+        }
+    }
+
+    // how fast |z| grows far out, for the smooth count
+    pub fn degree(&self, options: &RenderOptions) -> f64 {
+        match self {
+            Family::Phoenix => options.raised_to().degree(),
+            _ => 2.0,
+        }
+    }
+
+    // the parameter plane starts at a critical point
+    fn critical_point(&self) -> Complex {
+        match self {
+            Family::Lambda => Complex::new(0.5, 0.0),
+            _ => Complex::null(),
+        }
+    }
+
+    fn orbit(&self, start: Complex, c: Complex, in_plane: bool, options: &RenderOptions) -> Escape {
+        let dc = if in_plane { Complex::new(1.0, 0.0) } else { Complex::null() };
+        let step = FamilyStep { family: *self, exponent: options.raised_to(), phoenix: options.phoenix, c, dc };
+        // only phoenix adds c after the step, the others take it inside the map
+        let added = if *self == Family::Phoenix { c } else { Complex::null() };
+        orbit(&step, start, added, in_plane, options)
+    }
+}
+
+struct FamilyStep {
+    family: Family,
+    exponent: Exponent,
+    phoenix: Complex,
+    c: Complex,
+    // derivative of c with respect to the pixel
+    dc: Complex,
+}
+
+impl Step for FamilyStep {
+    fn apply(&self, z: Complex, previous: Complex) -> Complex {
+        match self.family {
+            Family::Phoenix => self.exponent.raise(z) + self.phoenix * previous,
+            Family::MagnetI | Family::MagnetII => {
+                let (n, _, d, _) = self.magnet(z, Complex::null());
+                match quotient(n, Complex::null(), d, Complex::null()) {
+                    Some((g, _)) => g * g,
+                    // the pole goes to infinity
+                    None => Complex::new(f64::INFINITY, 0.0),
+                }
+            }
+            Family::Lambda => self.c * z * (Complex::new(1.0, 0.0) - z),
+        }
+    }
+
+    fn derivative(&self, z: Complex, dz: Complex, previous: Complex) -> Complex {
+        let one = Complex::new(1.0, 0.0);
+        match self.family {
+            Family::Phoenix => self.exponent.derivative(z, dz) + self.phoenix * previous + self.dc,
+            Family::MagnetI | Family::MagnetII => {
+                let (n, dn, d, dd) = self.magnet(z, dz);
+                match quotient(n, dn, d, dd) {
+                    Some((g, dg)) => Complex::new(2.0, 0.0) * g * dg,
+                    None => Complex::null(),
+                }
+            }
+            Family::Lambda => self.c * (one - Complex::new(2.0, 0.0) * z) * dz + self.dc * z * (one - z),
+        }
+    }
+
+    fn radius(&self) -> f64 {
+        match self.family {
+            Family::MagnetI | Family::MagnetII => MAGNET_RADIUS,
+            // |c z (1 - z)| >= |c| |z| (|z| - 1) >= 2 |z| past 1 + 2 / |c|
+            Family::Lambda if self.c == Complex::null() => f64::INFINITY,
+            Family::Lambda => 1.0 + 2.0 / self.c.dist_from_origin(),
+            Family::Phoenix => 0.0,
+        }
+    }
+
+    fn attractor(&self) -> Option<Complex> {
+        match self.family {
+            Family::MagnetI | Family::MagnetII => Some(Complex::new(1.0, 0.0)),
+            _ => None,
+        }
+    }
+}
+
+impl FamilyStep {
+    // numerator and denominator of the magnet maps before squaring, with their derivatives
+    fn magnet(&self, z: Complex, dz: Complex) -> (Complex, Complex, Complex, Complex) {
+        let (c, dc) = (self.c, self.dc);
+        let real = |x: f64| Complex::new(x, 0.0);
+        if self.family == Family::MagnetI {
+            let n = z * z + c - real(1.0);
+            let d = real(2.0) * z + c - real(2.0);
+            return (n, real(2.0) * z * dz + dc, d, real(2.0) * dz + dc);
+        }
+        let constant = (c - real(1.0)) * (c - real(2.0));
+        // both depend on c through 3z + 2c - 3
+        let through_c = (real(3.0) * z + real(2.0) * c - real(3.0)) * dc;
+        let n = z * z * z + real(3.0) * (c - real(1.0)) * z + constant;
+        let dn = (real(3.0) * z * z + real(3.0) * (c - real(1.0))) * dz + through_c;
+        let d = real(3.0) * z * z + real(3.0) * (c - real(2.0)) * z + constant + real(1.0);
+        let dd = (real(6.0) * z + real(3.0) * (c - real(2.0))) * dz + through_c;
+        (n, dn, d, dd)
+    }
+}
+
+// n / d and its derivative, None at a pole
+fn quotient(n: Complex, dn: Complex, d: Complex, dd: Complex) -> Option<(Complex, Complex)> {
+    if d == Complex::null() {
+        return None;
+    }
+    Some((n / d, (dn * d - n * dd) / (d * d)))
+}
+
+// parameter plane of the family: the pixel is c and the orbit starts at a critical point
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FamilyPlane(pub Family);
+
+impl Fractal for FamilyPlane {
+    fn stable(&self, c: Complex, options: &RenderOptions) -> Escape {
+        self.0.orbit(self.0.critical_point(), c, true, options)
+    }
+}
+
+// dynamic plane of the family for c, the pixel is the start
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FamilyDynamic(pub Family, pub Complex);
+
+impl Fractal for FamilyDynamic {
+    fn stable(&self, start: Complex, options: &RenderOptions) -> Escape {
+        self.0.orbit(start, self.1, false, options)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Family, FamilyPlane, FamilyDynamic, FamilyStep};
+    use super::super::{Fractal, RenderOptions, Step, mandelbrot, formula::Exponent, complex::Complex};
+
+    #[test]
+    fn derivatives_of_the_maps() {
+        let h = 1e-7;
+        let (z, c) = (Complex::new(0.3, -0.6), Complex::new(1.2, 0.7));
+        for family in [Family::MagnetI, Family::MagnetII, Family::Lambda] {
+            let step = |c| FamilyStep { family, exponent: Exponent::Power(1), phoenix: Complex::null(), c, dc: Complex::new(1.0, 0.0) };
+            let (moved, along) = (Complex::new(h, 0.0), Complex::new(1.0 / h, 0.0));
+            // in z with c fixed, then in c
+            let dz = (step(c).apply(z + moved, Complex::null()) - step(c).apply(z, Complex::null())) * along;
+            let dc = (step(c + moved).apply(z, Complex::null()) - step(c).apply(z, Complex::null())) * along;
+            let derivative = step(c).derivative(z, Complex::new(1.0, 0.0), Complex::null());
+            assert!((derivative - dz - dc).dist_from_origin() < 1e-5, "{:?}", family);
+        }
+    }
+
+    #[test]
+    fn magnets_converge_to_one() {
+        let options = RenderOptions::new(200, 1);
+        for family in [Family::MagnetI, Family::MagnetII] {
+            // far out 1 attracts everything
+            let escape = FamilyPlane(family).stable(Complex::new(-8.0, 6.0), &options);
+            assert!(escape.converged.is_some_and(|converged| converged.root == Some(0)), "{:?}", family);
+            assert!((escape.z - Complex::new(1.0, 0.0)).dist_from_origin() < 1e-9);
+            // in the middle the critical orbit finds other cycles
+            let escape = FamilyPlane(family).stable(Complex::new(1.0, 0.5), &options);
+            assert!(escape.converged.is_none() && escape.iterations == options.tries, "{:?}", family);
+        }
+        assert!(FamilyPlane(Family::MagnetI).stable(Complex::new(1.5, 1.5), &options).iterations < 10);
+        // starts near 1 get pulled in
+        assert!(FamilyDynamic(Family::MagnetI, Complex::new(0.5, 0.5)).stable(Complex::new(1.1, 0.0), &options).converged.is_some());
+    }
+
+    #[test]
+    fn phoenix_remembers_the_previous_z() {
+        let mut options = RenderOptions::new(100, 1);
+        options.periodicity = false;
+        // without the previous z it's the mandelbrot set
+        options.phoenix = Complex::null();
+        for c in [Complex::new(0.3, 0.5), Complex::new(0.3, 0.6), Complex::new(-1.2, 0.1)] {
+            let (phoenix, plain) = (FamilyPlane(Family::Phoenix).stable(c, &options), mandelbrot(c.real(), c.imag(), &options));
+            assert_eq!((phoenix.iterations, phoenix.z), (plain.iterations, plain.z));
+        }
+        // 0 attracts for small c, |p| < 1 keeps the previous z from blowing it up
+        options.phoenix = Complex::new(-0.5, 0.0);
+        let julia = FamilyDynamic(Family::Phoenix, Complex::new(0.1, 0.0));
+        assert_eq!(julia.stable(Complex::null(), &options).iterations, 100);
+        assert!(julia.stable(Complex::new(1.5, 0.5), &options).iterations < 10);
+    }
+
+    #[test]
+    fn lambda_is_conjugate_to_the_mandelbrot_set() {
+        let options = RenderOptions::new(200, 1);
+        // lambda z (1 - z) is conjugate to w^2 + c with c = lambda / 2 - lambda^2 / 4
+        for lambda in [Complex::new(1.0, 0.5), Complex::new(3.2, 0.0), Complex::new(2.0, 2.0), Complex::new(4.5, 0.2)] {
+            let c = lambda * Complex::new(0.5, 0.0) - lambda * lambda * Complex::new(0.25, 0.0);
+            let escaped = FamilyPlane(Family::Lambda).stable(lambda, &options).iterations < options.tries;
+            assert_eq!(escaped, mandelbrot(c.real(), c.imag(), &options).iterations < options.tries, "{:?}", lambda);
+        }
+        assert_eq!(Family::from_name("logistic"), Some(Family::Lambda));
+    }
+}
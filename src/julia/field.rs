@@ -0,0 +1,112 @@
+use super::{Escape, RenderOptions};
+use super::complex::Complex;
+
+/// Escapes of every pixel of a frame together with the options they were computed with,
+/// so the frame can be colored again without iterating a single point.
+#[derive(Debug, Clone)]
+pub struct Field {
+    width: u32,
+    height: u32,
+    // rows from top to bottom, like the pixels of an image
+    escapes: Vec<Escape>,
+    // distance between neighbouring pixels, in the units the derivatives were taken in
+    pixel: f64,
+    options: RenderOptions,
+}
+
+impl Field {
+    // takes the columns the renderers produce
+    pub fn from_columns(columns: Vec<Vec<Escape>>, pixel: f64, options: &RenderOptions) -> Self {
+        let width = columns.len();
+        let height = columns.first().map_or(0, |column| column.len());
+        let mut escapes = Vec::with_capacity(width * height);
+        for y in 0..height {
+            escapes.extend(columns.iter().map(|column| column[y]));
+        }
+        Field {
+            width: width as u32,
+            height: height as u32,
+            escapes,
+            pixel,
+            options: options.clone(),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixel(&self) -> f64 {
+        self.pixel
+    }
+
+    pub fn options(&self) -> &RenderOptions {
+        &self.options
+    }
+
+    // (x, y, escape) row by row
+    pub fn iter(&self) -> impl Iterator<Item = (u32, u32, &Escape)> {
+        let width = self.width;
+        self.escapes.iter().enumerate().map(move |(i, escape)| (i as u32 % width, i as u32 / width, escape))
+    }
+
+    // points that never escaped
+    pub fn is_interior(&self, escape: &Escape) -> bool {
+        escape.iterations == self.options.tries
+    }
+
+    pub fn counts(&self) -> Vec<u32> {
+        self.escapes.iter().map(|escape| escape.iterations).collect()
+    }
+
+    // normalized iteration counts, whether the field was rendered smooth or not
+    pub fn smooth(&self) -> Vec<f64> {
+        let options = &self.options;
+        self.escapes.iter().map(|escape| options.smooth_count(escape)).collect()
+    }
+
+    pub fn final_z(&self) -> Vec<Complex> {
+        self.escapes.iter().map(|escape| escape.z).collect()
+    }
+
+    // distance estimate to the set in pixels, None for the interior or without derivatives
+    pub fn distance(&self, escape: &Escape) -> Option<f64> {
+        if self.is_interior(escape) {
+            return None;
+        }
+        escape.distance().map(|distance| distance / self.pixel)
+    }
+
+    // distances in pixels, NaN where there is none
+    pub fn distances(&self) -> Vec<f64> {
+        self.escapes.iter().map(|escape| self.distance(escape).unwrap_or(f64::NAN)).collect()
+    }
+
+    // closest approaches to the orbit trap, NaN where there is none
+    pub fn traps(&self) -> Vec<f64> {
+        self.escapes.iter().map(|escape| escape.trap.unwrap_or(f64::NAN)).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Field;
+    use super::super::{Escape, RenderOptions};
+    use super::super::complex::Complex;
+
+    #[test]
+    fn columns_become_rows() {
+        let escape = |i| Escape::new(i, Complex::new(i as f64, 0.0));
+        let columns = vec![vec![escape(0), escape(1)], vec![escape(2), escape(3)], vec![escape(4), escape(5)]];
+        let field = Field::from_columns(columns, 0.1, &RenderOptions::new(5, 1));
+        assert_eq!((field.width(), field.height()), (3, 2));
+        assert_eq!(field.counts(), vec![0, 2, 4, 1, 3, 5]);
+        assert!(field.is_interior(&field.escapes[5]));
+        assert_eq!(field.iter().nth(4).map(|(x, y, e)| (x, y, e.iterations)), Some((1, 1, 3)));
+        assert_eq!(field.final_z()[1], Complex::new(2.0, 0.0));
+    }
+}
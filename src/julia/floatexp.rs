@@ -0,0 +1,375 @@
+use std::cmp::Ordering;
+use std::ops::{Add, AddAssign, Sub, SubAssign, Mul, Div, Neg};
+
+use super::complex::Complex;
+
+const MANTISSA_MASK: u64 = !(0x7ff << 52);
+// biased exponent of numbers in [0.5, 1)
+const HALF_EXP: u64 = 1022;
+
+/// f64 mantissa in [0.5, 1) with a separate exponent: mantissa * 2^exponent.
+/// Covers numbers way smaller than 1e-308 for about the cost of a few f64 operations.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FloatExp {
+    mantissa: f64,
+    exponent: i64,
+}
+
+impl FloatExp {
+    pub fn new(x: f64) -> Self {
+        Self::from_parts(x, 0)
+    }
+
+    pub fn from_parts(mantissa: f64, exponent: i64) -> Self {
+        if mantissa == 0.0 || !mantissa.is_finite() {
+            return Self { mantissa, exponent: 0 };
+        }
+        let mut mantissa = mantissa;
+        let mut exponent = exponent;
+        let mut biased = (mantissa.to_bits() >> 52) & 0x7ff;
+        if biased == 0 {
+            // subnormal, move it into the normal range first
+            mantissa *= ldexp(1.0, 54);
+            exponent -= 54;
+            biased = (mantissa.to_bits() >> 52) & 0x7ff;
+        }
+        Self {
+            mantissa: f64::from_bits((mantissa.to_bits() & MANTISSA_MASK) | (HALF_EXP << 52)),
+            exponent: exponent + biased as i64 - HALF_EXP as i64,
+        }
+    }
+
+    pub fn zero() -> Self {
+        Self { mantissa: 0.0, exponent: 0 }
+    }
+
+    // rounds to 0.0 / infinity outside of the f64 range
+    pub fn to_f64(self) -> f64 {
+        ldexp(self.mantissa, self.exponent)
+    }
+
+    // exponent of the value in base 2, i64::MIN for zero
+    pub fn exponent(&self) -> i64 {
+        if self.mantissa == 0.0 {
+            i64::MIN
+        } else {
+            self.exponent
+        }
+    }
+
+    pub fn sqrt(self) -> Self {
+        if self.exponent % 2 == 0 {
+            Self::from_parts(self.mantissa.sqrt(), self.exponent / 2)
+        } else {
+            Self::from_parts((2.0 * self.mantissa).sqrt(), (self.exponent - 1) / 2)
+        }
+    }
+}
+
+// x * 2^exp without overflowing the intermediate power of two
+fn ldexp(x: f64, exp: i64) -> f64 {
+    if exp > 1023 {
+        if exp > 2 * 1023 {
+            x * f64::INFINITY
+        } else {
+            x * pow2(1023) * pow2(exp - 1023)
+        }
+    } else if exp < -1022 {
+        if exp < -2 * 1022 {
+            x * 0.0
+        } else {
+            // the last multiplication rounds into the subnormals
+            x * pow2(exp + 1022) * pow2(-1022)
+        }
+    } else {
+        x * pow2(exp)
+    }
+}
+
+// 2^exp for exp in [-1022, 1023]
+fn pow2(exp: i64) -> f64 {
+    f64::from_bits(((exp + 1023) as u64) << 52)
+}
+
+impl From<f64> for FloatExp {
+    fn from(x: f64) -> Self {
+        Self::new(x)
+    }
+}
+
+impl Add for FloatExp {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        if self.mantissa == 0.0 {
+            return other;
+        }
+        if other.mantissa == 0.0 {
+            return self;
+        }
+        let (big, small) = if self.exponent >= other.exponent { (self, other) } else { (other, self) };
+        let shift = small.exponent - big.exponent;
+        if shift < -64 {
+            // small is below the precision of big
+            big
+        } else {
+            Self::from_parts(big.mantissa + ldexp(small.mantissa, shift), big.exponent)
+        }
+    }
+}
+
+impl Sub for FloatExp {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self + (-other)
+    }
+}
+
+impl Mul for FloatExp {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Self::from_parts(self.mantissa * other.mantissa, self.exponent + other.exponent)
+    }
+}
+
+impl Div for FloatExp {
+    type Output = Self;
+
+    fn div(self, other: Self) -> Self {
+        Self::from_parts(self.mantissa / other.mantissa, self.exponent - other.exponent)
+    }
+}
+
+impl Neg for FloatExp {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self { mantissa: - self.mantissa, exponent: self.exponent }
+    }
+}
+
+impl PartialOrd for FloatExp {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        let sign = self.mantissa.signum();
+        if self.mantissa == 0.0 || other.mantissa == 0.0 || sign != other.mantissa.signum() {
+            return self.mantissa.partial_cmp(&other.mantissa);
+        }
+        match self.exponent.cmp(&other.exponent) {
+            Ordering::Equal => self.mantissa.partial_cmp(&other.mantissa),
+            // bigger exponent means bigger absolute value
+            ord if sign > 0.0 => Some(ord),
+            ord => Some(ord.reverse()),
+        }
+    }
+}
+
+/// Complex number made of FloatExp, mirrors julia::complex::Complex
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ComplexExp {
+    real: FloatExp,
+    imag: FloatExp,
+}
+
+impl ComplexExp {
+    pub fn new(real: FloatExp, imag: FloatExp) -> Self {
+        Self { real, imag }
+    }
+
+    pub fn null() -> Self {
+        Self {
+            real: FloatExp::zero(),
+            imag: FloatExp::zero(),
+        }
+    }
+
+    pub fn con(&self) -> Self {
+        Self {
+            real: self.real,
+            imag: - self.imag,
+        }
+    }
+
+    pub fn dist_from_origin(&self) -> FloatExp {
+        (self.real * self.real + self.imag * self.imag).sqrt()
+    }
+
+    // only the tests compare against Complex::powi, the kernels square FloatExp by multiplying
+    #[cfg(test)]
+    pub fn powi(self, exponent: u32) -> Self {
+        if exponent == 0 {
+            ComplexExp::new(FloatExp::new(1.0), FloatExp::zero())
+        } else {
+            let mut z = self;
+            for _ in 0..exponent {
+                z = z * self;
+            }
+            z
+        }
+    }
+
+    // exponent of the bigger part
+    pub fn exponent(&self) -> i64 {
+        self.real.exponent().max(self.imag.exponent())
+    }
+
+    pub fn to_complex(self) -> Complex {
+        Complex::new(self.real.to_f64(), self.imag.to_f64())
+    }
+}
+
+impl From<Complex> for ComplexExp {
+    fn from(c: Complex) -> Self {
+        Self::new(FloatExp::new(c.real()), FloatExp::new(c.imag()))
+    }
+}
+
+impl Add for ComplexExp {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            real: self.real + other.real,
+            imag: self.imag + other.imag,
+        }
+    }
+}
+
+impl AddAssign for ComplexExp {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for ComplexExp {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self {
+            real: self.real - other.real,
+            imag: self.imag - other.imag,
+        }
+    }
+}
+
+impl SubAssign for ComplexExp {
+    fn sub_assign(&mut self, other: Self) {
+        *self = *self - other;
+    }
+}
+
+impl Mul for ComplexExp {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Self {
+            real: self.real * other.real - self.imag * other.imag,
+            imag: self.imag * other.real + self.real * other.imag,
+        }
+    }
+}
+
+impl Div for ComplexExp {
+    type Output = Self;
+
+    fn div(self, other: Self) -> Self {
+        let con = other.con();
+        let num = self * con;
+        let denom = (other * con).real;
+        Self {
+            real: num.real / denom,
+            imag: num.imag / denom,
+        }
+    }
+}
+
+impl Neg for ComplexExp {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {
+            real: - self.real,
+            imag: - self.imag,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FloatExp, ComplexExp};
+    use super::super::complex::Complex;
+
+    fn c(real: f64, imag: f64) -> ComplexExp {
+        ComplexExp::from(Complex::new(real, imag))
+    }
+
+    #[test]
+    fn normalize() {
+        assert_eq!(FloatExp::new(1.0), FloatExp::from_parts(0.5, 1));
+        assert_eq!(FloatExp::new(-6.0), FloatExp::from_parts(-0.75, 3));
+        assert_eq!(FloatExp::new(0.0), FloatExp::zero());
+        assert_eq!(FloatExp::new(5e-324).to_f64(), 5e-324);
+        assert_eq!(FloatExp::new(1.5e300).to_f64(), 1.5e300);
+    }
+
+    #[test]
+    fn beyond_f64() {
+        let tiny = FloatExp::new(1e-300) * FloatExp::new(1e-300);
+        assert_eq!(tiny.to_f64(), 0.0);
+        assert!(tiny > FloatExp::zero());
+        let back = tiny / FloatExp::new(1e-300);
+        assert!((back.to_f64() - 1e-300).abs() < 1e-310);
+        let sum = tiny + tiny;
+        assert_eq!((sum / tiny).to_f64(), 2.0);
+        assert_eq!((tiny - tiny).to_f64(), 0.0);
+        assert!((((tiny * tiny).sqrt() / tiny).to_f64() - 1.0).abs() < 1e-15);
+    }
+
+    #[test]
+    fn ordering() {
+        let values = [-3.0, -1e-200, 0.0, 1e-250, 0.25, 7.0];
+        for (i, a) in values.iter().enumerate() {
+            for (j, b) in values.iter().enumerate() {
+                assert_eq!(FloatExp::new(*a).partial_cmp(&FloatExp::new(*b)), i.partial_cmp(&j));
+            }
+        }
+    }
+
+    #[test]
+    fn complex_add_sub() {
+        let a = c(1.0, 0.0);
+        let b = c(0.0, 1.0);
+        let d = c(-2.0, -6.0);
+
+        assert_eq!(a + b, c(1.0, 1.0));
+        assert_eq!(a + d, c(-1.0, -6.0));
+        assert_eq!(b - d, c(2.0, 7.0));
+
+        let mut e = a;
+        e += b;
+        e -= d;
+        assert_eq!(e, c(3.0, 7.0));
+    }
+
+    #[test]
+    fn complex_mul_div() {
+        let a = c(1.0,0.0);
+        let b = c(0.0,1.0);
+        let x = c(2.0,3.0);
+        let d = c(-5.0,-4.0);
+
+        assert_eq!(a * b, c(0.0, 1.0));
+        assert_eq!(x * d, c(2.0, -23.0));
+        assert_eq!(x / b, c(3.0,-2.0));
+        assert_eq!((x / d).to_complex(), Complex::new(2.0, 3.0) / Complex::new(-5.0, -4.0));
+    }
+
+    #[test]
+    fn complex_matches_f64() {
+        let x = Complex::new(0.3, -1.7);
+        assert_eq!(ComplexExp::from(x).powi(3).to_complex(), x.powi(3));
+        assert_eq!(ComplexExp::from(x).dist_from_origin().to_f64(), x.dist_from_origin());
+        assert_eq!((-ComplexExp::from(x)).con().to_complex(), Complex::new(-0.3, -1.7));
+    }
+}
@@ -0,0 +1,160 @@
+use super::complex::Complex;
+use super::derivative;
+use super::perturbation::degree;
+
+// what z gets raised to
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Exponent {
+    // Complex::powi style, 1 is z^2
+    Power(u32),
+    // any real or complex exponent, on the principal branch of Complex::powc
+    Complex(Complex),
+}
+
+impl Exponent {
+    pub fn raise(&self, z: Complex) -> Complex {
+        match *self {
+            // the plain power exactly like the kernels always took it
+            Exponent::Power(power) => z.powi(power),
+            Exponent::Complex(exponent) => z.powc(exponent),
+        }
+    }
+
+    // derivative of z^e at z times dz, on the same branch as raise
+    pub fn derivative(&self, z: Complex, dz: Complex) -> Complex {
+        match *self {
+            Exponent::Power(power) => derivative(z, dz, power),
+            Exponent::Complex(_) if z == Complex::null() => Complex::null(),
+            Exponent::Complex(exponent) => exponent * z.powc(exponent - Complex::new(1.0, 0.0)) * dz,
+        }
+    }
+
+    // how fast |z| grows far out, log |z^e| / log |z| tends to the real part
+    pub fn degree(&self) -> f64 {
+        match *self {
+            Exponent::Power(power) => degree(power) as f64,
+            Exponent::Complex(exponent) => exponent.real(),
+        }
+    }
+}
+
+// escape time formula z -> f(z) + c, the variants fold z before or after taking the power
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Formula {
+    // z^d
+    Mandelbrot,
+    // (|x| + i|y|)^d
+    BurningShip,
+    // conj(z)^d, also known as the mandelbar set
+    Tricorn,
+    // z^d with the real part made positive
+    Celtic,
+    // (|x| - iy)^d
+    Perpendicular,
+    // z^d with both parts made positive
+    Buffalo,
+}
+
+impl Formula {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "mandelbrot" => Some(Formula::Mandelbrot),
+            "burning_ship" => Some(Formula::BurningShip),
+            "tricorn" | "mandelbar" => Some(Formula::Tricorn),
+            "celtic" => Some(Formula::Celtic),
+            "perpendicular" => Some(Formula::Perpendicular),
+            "buffalo" => Some(Formula::Buffalo),
+            _ => None,
+        }
+    }
+
+    pub fn apply(&self, z: Complex, exponent: Exponent) -> Complex {
+        let w = exponent.raise(flip(z, self.before(z)));
+        flip(w, self.after(w))
+    }
+
+    // f'(z) dz. The folds aren't holomorphic, for them dz is a real direction of the plane
+    // and gets folded like z, which is what the distance estimates need
+    pub fn derivative(&self, z: Complex, dz: Complex, exponent: Exponent) -> Complex {
+        let before = self.before(z);
+        let u = flip(z, before);
+        flip(exponent.derivative(u, flip(dz, before)), self.after(exponent.raise(u)))
+    }
+
+    // signs the parts of z get multiplied with before taking the power
+    fn before(&self, z: Complex) -> (f64, f64) {
+        match self {
+            Formula::BurningShip => (z.real().signum(), z.imag().signum()),
+            Formula::Tricorn => (1.0, -1.0),
+            Formula::Perpendicular => (z.real().signum(), -1.0),
+            _ => (1.0, 1.0),
+        }
+    }
+
+    // signs for the parts of the power w
+    fn after(&self, w: Complex) -> (f64, f64) {
+        match self {
+            Formula::Celtic => (w.real().signum(), 1.0),
+            Formula::Buffalo => (w.real().signum(), w.imag().signum()),
+            _ => (1.0, 1.0),
+        }
+    }
+}
+
+fn flip(z: Complex, signs: (f64, f64)) -> Complex {
+    Complex::new(signs.0 * z.real(), signs.1 * z.imag())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Formula, Exponent};
+    use super::super::complex::Complex;
+
+    #[test]
+    fn folds() {
+        let z = Complex::new(-1.0, 2.0);
+        let square = Exponent::Power(1);
+        // z^2 = -3 - 4i
+        assert_eq!(Formula::Mandelbrot.apply(z, square), Complex::new(-3.0, -4.0));
+        assert_eq!(Formula::BurningShip.apply(z, square), Complex::new(-3.0, 4.0));
+        assert_eq!(Formula::Tricorn.apply(z, square), Complex::new(-3.0, 4.0));
+        assert_eq!(Formula::Celtic.apply(z, square), Complex::new(3.0, -4.0));
+        assert_eq!(Formula::Perpendicular.apply(z, square), Complex::new(-3.0, -4.0));
+        assert_eq!(Formula::Perpendicular.apply(Complex::new(1.0, 2.0), square), Complex::new(-3.0, -4.0));
+        assert_eq!(Formula::Buffalo.apply(z, square), Complex::new(3.0, 4.0));
+        assert_eq!(Formula::BurningShip.apply(z, Exponent::Power(2)), Complex::new(-11.0, -2.0));
+        assert_eq!(Formula::from_name("mandelbar"), Some(Formula::Tricorn));
+        assert_eq!(Formula::from_name("ship"), None);
+    }
+
+    #[test]
+    fn derivatives_follow_the_folds() {
+        let h = 1e-7;
+        let z = Complex::new(-0.7, 0.4);
+        let cube = Exponent::Power(2);
+        for formula in [Formula::BurningShip, Formula::Tricorn, Formula::Celtic, Formula::Perpendicular, Formula::Buffalo] {
+            for dz in [Complex::new(1.0, 0.0), Complex::new(0.0, 1.0)] {
+                let moved = z + dz * Complex::new(h, 0.0);
+                let difference = (formula.apply(moved, cube) - formula.apply(z, cube)) * Complex::new(1.0 / h, 0.0);
+                assert!((difference - formula.derivative(z, dz, cube)).dist_from_origin() < 1e-5, "{:?}", formula);
+            }
+        }
+    }
+
+    #[test]
+    fn complex_exponents() {
+        let z = Complex::new(-0.7, 0.4);
+        // whole exponents agree with the powers
+        let (power, exponent) = (Exponent::Power(2), Exponent::Complex(Complex::new(3.0, 0.0)));
+        assert!((Formula::Mandelbrot.apply(z, power) - Formula::Mandelbrot.apply(z, exponent)).dist_from_origin() < 1e-12);
+        assert!((Formula::BurningShip.apply(z, power) - Formula::BurningShip.apply(z, exponent)).dist_from_origin() < 1e-12);
+        assert_eq!(exponent.degree(), 3.0);
+        // away from the branch cut the derivative is the limit of the differences
+        let h = 1e-7;
+        for exponent in [Exponent::Complex(Complex::new(2.5, 0.0)), Exponent::Complex(Complex::new(2.0, 0.3))] {
+            let difference = (exponent.raise(z + Complex::new(h, 0.0)) - exponent.raise(z)) * Complex::new(1.0 / h, 0.0);
+            assert!((difference - exponent.derivative(z, Complex::new(1.0, 0.0))).dist_from_origin() < 1e-5);
+        }
+        assert_eq!(Exponent::Complex(Complex::new(2.5, 0.0)).derivative(Complex::null(), Complex::new(1.0, 0.0)), Complex::null());
+    }
+}
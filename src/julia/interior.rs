@@ -0,0 +1,155 @@
+use std::f64::consts::PI;
+
+use super::complex::Complex;
+use super::perturbation::degree;
+use super::pow;
+
+// what points that never escape get colored by
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Interior {
+    Black,
+    // |z| after the last try
+    Magnitude,
+    // smallest |z| the orbit came by
+    Minimum,
+    // length of the attracting cycle, needs the cycle to be detected
+    Period,
+    // distance to the boundary of the mandelbrot set, julia sets stay black
+    Distance,
+    // angle of the multiplier of the attracting cycle
+    Multiplier,
+}
+
+impl Interior {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "black" => Some(Interior::Black),
+            "magnitude" => Some(Interior::Magnitude),
+            "minimum" => Some(Interior::Minimum),
+            "period" => Some(Interior::Period),
+            "distance" => Some(Interior::Distance),
+            "multiplier" => Some(Interior::Multiplier),
+            _ => None,
+        }
+    }
+
+    // the attracting cycle has to be found even without periodicity checking
+    pub fn needs_cycle(&self) -> bool {
+        matches!(self, Interior::Period | Interior::Distance | Interior::Multiplier)
+    }
+
+    // every interior pixel of a component gets the same color
+    pub fn is_uniform(&self) -> bool {
+        matches!(self, Interior::Black | Interior::Period)
+    }
+
+    // value the kernels store with interior escapes, the other modes only need the escape itself.
+    // z is on the cycle of the given period under z^d + c, in_plane is false for julia sets
+    pub fn value(&self, z: Complex, c: Complex, in_plane: bool, period: Option<u32>, minimum: f64, power: u32) -> Option<f64> {
+        match (self, period) {
+            (Interior::Minimum, _) => Some(minimum),
+            (Interior::Distance, Some(period)) if in_plane => Cycle::new(z, c, period, power).distance(),
+            (Interior::Multiplier, Some(period)) => {
+                let multiplier = Cycle::new(z, c, period, power).multiplier;
+                Some(multiplier.imag().atan2(multiplier.real()))
+            }
+            _ => None,
+        }
+    }
+
+    // position on the palette in [0, 1), None if the interior stays black
+    pub fn position(&self, z: Complex, value: Option<f64>, period: Option<u32>, pixel: f64) -> Option<f64> {
+        let t = match self {
+            Interior::Black => return None,
+            // the orbits of the set stay within |z| <= 2
+            Interior::Magnitude => z.dist_from_origin() / 2.0,
+            Interior::Minimum => value? / 2.0,
+            // golden ratio steps keep neighbouring periods apart
+            Interior::Period => period? as f64 * 0.618_033_988_749_895,
+            // one run through the palette over 10 doublings of the distance in pixels
+            Interior::Distance => (1.0 + value? / pixel).log2() / 10.0,
+            Interior::Multiplier => value? / (2.0 * PI) + 0.5,
+        };
+        Some(t.rem_euclid(1.0))
+    }
+}
+
+// derivatives of the period-th iterate of z^d + c at a point of its cycle
+struct Cycle {
+    // d/dz
+    multiplier: Complex,
+    // d/dc
+    dc: Complex,
+    // d^2/dz^2
+    dzdz: Complex,
+    // d^2/dc dz
+    dcdz: Complex,
+}
+
+impl Cycle {
+    fn new(z: Complex, c: Complex, period: u32, power: u32) -> Self {
+        let d = degree(power);
+        let one = Complex::new(1.0, 0.0);
+        let (mut z, mut dz, mut dc, mut dzdz, mut dcdz) = (z, one, Complex::null(), Complex::null(), Complex::null());
+        for _ in 0..period {
+            let first = pow(z, d.saturating_sub(1)) * Complex::new(d as f64, 0.0);
+            let second = pow(z, d.saturating_sub(2)) * Complex::new((d * d.saturating_sub(1)) as f64, 0.0);
+            dzdz = second * dz * dz + first * dzdz;
+            dcdz = second * dc * dz + first * dcdz;
+            dz = first * dz;
+            dc = first * dc + one;
+            z = pow(z, d) + c;
+        }
+        Cycle { multiplier: dz, dc, dzdz, dcdz }
+    }
+
+    // interior distance estimate (1 - |dz|^2) / |dcdz + dzdz dc / (1 - dz)|, only for attracting cycles
+    fn distance(&self) -> Option<f64> {
+        let one = Complex::new(1.0, 0.0);
+        let norm = self.multiplier.dist_from_origin();
+        if norm >= 1.0 {
+            return None;
+        }
+        let denominator = self.dcdz + self.dzdz * self.dc / (one - self.multiplier);
+        Some((1.0 - norm * norm) / denominator.dist_from_origin())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Cycle, Interior};
+    use super::super::complex::Complex;
+
+    #[test]
+    fn fixed_point_of_the_cardioid() {
+        // z^2 + c with c = 0: the fixed point 0 is superattracting
+        let cycle = Cycle::new(Complex::null(), Complex::null(), 1, 1);
+        assert_eq!(cycle.multiplier, Complex::null());
+        // the main cardioid reaches 0.25 on the real axis, the estimate is within a factor of 4
+        let distance = cycle.distance().unwrap();
+        assert!(distance > 0.25 / 4.0 && distance <= 0.25 * 4.0, "{}", distance);
+
+        // c = -1 has the superattracting 2 cycle 0, -1
+        let c = Complex::new(-1.0, 0.0);
+        let cycle = Cycle::new(Complex::null(), c, 2, 1);
+        assert_eq!(cycle.multiplier, Complex::null());
+        assert!(cycle.distance().unwrap() > 0.0);
+    }
+
+    #[test]
+    fn multiplier_angle() {
+        // c = 0.25i: fixed point of z^2 + c has multiplier 2z
+        let c = Complex::new(0.0, 0.25);
+        let mut z = Complex::null();
+        for _ in 0..200 {
+            z = z * z + c;
+        }
+        let angle = Interior::Multiplier.value(z, c, true, Some(1), 0.0, 1).unwrap();
+        assert!((angle - z.imag().atan2(z.real())).abs() < 1e-12);
+        // julia sets have no distance, but the same multiplier
+        assert_eq!(Interior::Multiplier.value(z, c, false, Some(1), 0.0, 1), Some(angle));
+        assert_eq!(Interior::Distance.value(z, c, false, Some(1), 0.0, 1), None);
+        assert_eq!(Interior::Period.position(z, None, None, 1.0), None);
+        assert_eq!(Interior::Black.position(z, None, Some(3), 1.0), None);
+    }
+}
@@ -0,0 +1,206 @@
+use super::complex::Complex;
+use super::polynomial::{Polynomial, PolynomialError};
+use super::{CycleCheck, Escape, Fractal, RenderOptions};
+
+use std::sync::Arc;
+
+// orbits whose steps get shorter than this have converged
+pub const TOLERANCE: f64 = 1e-9;
+
+// orbits that converge closer than this to one of the roots count as reaching it
+const ROOT_MATCH: f64 = 1e-4;
+
+// orbits that get this far out are taken as lost, the methods blow up where p' vanishes
+const DIVERGED: f64 = 1e10;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Method {
+    // z - a p / p', quadratic convergence
+    Newton,
+    // z - a 2 p p' / (2 p'^2 - p p''), cubic convergence
+    Halley,
+}
+
+impl Method {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "newton" => Some(Method::Newton),
+            "halley" => Some(Method::Halley),
+            _ => None,
+        }
+    }
+}
+
+// how the orbit of a root finding method ended up
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Converged {
+    // index of the root it reached, None for the fixed points nova orbits converge to
+    pub root: Option<u32>,
+    // iterations until the steps got shorter than the tolerance, with the fraction interpolated
+    // from the step lengths so it's continuous between pixels
+    pub speed: f64,
+}
+
+/// Root finding method for a polynomial. Points get colored by the root they converge to and how fast,
+/// with nova the pixel (or the julia point) gets added after every step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RootFinder {
+    polynomial: Polynomial,
+    roots: Vec<Complex>,
+    pub method: Method,
+    // a in the steps, 1 is the plain method
+    pub relaxation: Complex,
+    pub nova: bool,
+}
+
+impl RootFinder {
+    pub fn from_roots(roots: Vec<Complex>, method: Method, relaxation: Complex, nova: bool) -> Result<Self, PolynomialError> {
+        let polynomial = Polynomial::from_roots(&roots)?;
+        Ok(RootFinder { polynomial, roots, method, relaxation, nova })
+    }
+
+    pub fn from_coefficients(coefficients: Vec<Complex>, method: Method, relaxation: Complex, nova: bool) -> Result<Self, PolynomialError> {
+        let polynomial = Polynomial::new(coefficients, 0)?;
+        let roots = polynomial.roots();
+        Ok(RootFinder { polynomial, roots, method, relaxation, nova })
+    }
+
+    pub fn roots(&self) -> &[Complex] {
+        &self.roots
+    }
+
+    // z after one step of the method, None where it's undefined
+    fn step(&self, z: Complex) -> Option<Complex> {
+        let (p, dp, ddp) = self.polynomial.derivatives(z);
+        let denominator = match self.method {
+            Method::Newton => dp,
+            Method::Halley => dp * dp * Complex::new(2.0, 0.0) - p * ddp,
+        };
+        if denominator == Complex::null() {
+            return None;
+        }
+        let numerator = match self.method {
+            Method::Newton => p,
+            Method::Halley => p * dp * Complex::new(2.0, 0.0),
+        };
+        Some(z - self.relaxation * numerator / denominator)
+    }
+
+    fn root(&self, z: Complex) -> Option<u32> {
+        self.roots.iter().position(|&root| (z - root).dist_from_origin() < ROOT_MATCH).map(|i| i as u32)
+    }
+
+    // iterates from start, c only gets added with nova. Points that don't converge are interior
+    pub fn converge(&self, start: Complex, c: Complex, options: &RenderOptions) -> Escape {
+        let c = if self.nova { c } else { Complex::null() };
+        let mut z = start;
+        let mut last = f64::INFINITY;
+        let mut cycle = CycleCheck::new(z);
+        for i in 0..options.tries {
+            let next = match self.step(z) {
+                Some(next) => next + c,
+                None => break,
+            };
+            let length = (next - z).dist_from_origin();
+            z = next;
+            if length < TOLERANCE {
+                let converged = Converged { root: self.root(z), speed: speed(i, last, length) };
+                return Escape::new(i, z).with_converged(converged);
+            }
+            if z.dist_from_origin() > DIVERGED || z.dist_from_origin().is_nan() {
+                break;
+            }
+            last = length;
+            // attracting cycles of the method that aren't roots
+            if options.periodicity {
+                if let Some(period) = cycle.update(z) {
+                    return Escape::periodic(options.tries, z, period);
+                }
+            }
+        }
+        Escape::new(options.tries, z)
+    }
+
+    // a root of p is a critical point of the newton map, so nova's parameter plane starts there
+    fn critical_point(&self) -> Complex {
+        self.roots[0]
+    }
+}
+
+// speed of an orbit that converged in step i: where between the last two steps the length
+// crossed the tolerance, on a log scale
+pub fn speed(i: u32, last: f64, length: f64) -> f64 {
+    let fraction = if last.is_finite() && length > 0.0 {
+        ((TOLERANCE.ln() - last.ln()) / (length.ln() - last.ln())).clamp(0.0, 1.0)
+    } else {
+        1.0
+    };
+    i as f64 + fraction
+}
+
+// basins of the roots, the pixel is the start. c is added in every step with nova
+#[derive(Debug, Clone, PartialEq)]
+pub struct Newton(pub Arc<RootFinder>, pub Complex);
+
+impl Fractal for Newton {
+    fn stable(&self, start: Complex, options: &RenderOptions) -> Escape {
+        self.0.converge(start, self.1, options)
+    }
+}
+
+// parameter plane of nova: the pixel is c and the orbit starts at a root
+#[derive(Debug, Clone, PartialEq)]
+pub struct Nova(pub Arc<RootFinder>);
+
+impl Fractal for Nova {
+    fn stable(&self, c: Complex, options: &RenderOptions) -> Escape {
+        self.0.converge(self.0.critical_point(), c, options)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{RootFinder, Method, Newton, Nova};
+    use super::super::{Fractal, RenderOptions, complex::Complex};
+    use std::sync::Arc;
+
+    fn cube(method: Method, relaxation: f64, nova: bool) -> Arc<RootFinder> {
+        let coefficients = vec![Complex::new(-1.0, 0.0), Complex::null(), Complex::null(), Complex::new(1.0, 0.0)];
+        Arc::new(RootFinder::from_coefficients(coefficients, method, Complex::new(relaxation, 0.0), nova).unwrap())
+    }
+
+    #[test]
+    fn basins_of_the_roots() {
+        let options = RenderOptions::new(100, 1);
+        let finder = cube(Method::Newton, 1.0, false);
+        let one = finder.roots().iter().position(|&r| (r - Complex::new(1.0, 0.0)).dist_from_origin() < 1e-9).unwrap() as u32;
+        // close to a root it's reached fast, the other roots have their own basins
+        let escape = Newton(finder.clone(), Complex::null()).stable(Complex::new(1.1, 0.05), &options);
+        let converged = escape.converged.unwrap();
+        assert_eq!(converged.root, Some(one));
+        assert!(converged.speed < 8.0 && converged.speed >= escape.iterations as f64);
+        let other = Newton(finder.clone(), Complex::null()).stable(Complex::new(-1.0, 1.0), &options);
+        assert!(other.converged.unwrap().root.is_some_and(|root| root != one));
+        // the critical point 0 never gets anywhere
+        assert_eq!(Newton(finder.clone(), Complex::null()).stable(Complex::null(), &options).iterations, 100);
+
+        // halley converges faster, relaxation slower
+        let halley = Newton(cube(Method::Halley, 1.0, false), Complex::null()).stable(Complex::new(1.1, 0.05), &options);
+        let relaxed = Newton(cube(Method::Newton, 0.5, false), Complex::null()).stable(Complex::new(1.1, 0.05), &options);
+        assert!(halley.iterations < escape.iterations && relaxed.iterations > escape.iterations);
+        assert_eq!(halley.converged.unwrap().root, Some(one));
+        assert_eq!(relaxed.converged.unwrap().root, Some(one));
+    }
+
+    #[test]
+    fn nova_adds_the_pixel() {
+        let options = RenderOptions::new(200, 1);
+        let nova = Nova(cube(Method::Newton, 1.0, true));
+        // c = 0 is plain newton, started on a root it stays there
+        let escape = nova.stable(Complex::null(), &options);
+        assert_eq!(escape.iterations, 0);
+        // a small c moves the fixed point off the root
+        let escape = nova.stable(Complex::new(0.01, 0.0), &options);
+        assert!(escape.converged.is_some_and(|converged| converged.root.is_none()));
+    }
+}
@@ -0,0 +1,534 @@
+use super::complex::Complex;
+use super::{Escape, Bailout, RenderOptions, Control, derivative};
+use super::interior::Interior;
+use super::floatexp::ComplexExp;
+use dashu_float::FBig;
+use std::convert::TryFrom;
+
+// below this width f64 pixel coordinates can't tell neighbouring pixels apart anymore
+pub const DEEP_ZOOM_SPAN: f64 = 1e-13;
+
+// below this width pixel offsets underflow f64 and have to be iterated as FloatExp
+pub const FLOATEXP_SPAN: f64 = 1e-290;
+
+// dz above 2^-960 (~1e-289) leaves enough room in f64 for the rest of the iteration
+const F64_SAFE_EXPONENT: i64 = -960;
+
+pub fn big_from_f64(x: f64, precision: usize) -> FBig {
+    FBig::try_from(x).expect("cords have to be finite").with_precision(precision).value()
+}
+
+#[derive(Clone)]
+struct BigComplex {
+    real: FBig,
+    imag: FBig,
+}
+
+impl BigComplex {
+    fn new(real: FBig, imag: FBig) -> Self {
+        BigComplex{real, imag}
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        let real = &self.real * &other.real - &self.imag * &other.imag;
+        let imag = &self.real * &other.imag + &self.imag * &other.real;
+        BigComplex::new(real, imag)
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        BigComplex::new(&self.real + &other.real, &self.imag + &other.imag)
+    }
+
+    // same semantics as Complex::powi
+    fn powi(&self, exponent: u32, precision: usize) -> Self {
+        if exponent == 0 {
+            BigComplex::new(big_from_f64(1.0, precision), big_from_f64(0.0, precision))
+        } else {
+            let mut z = self.clone();
+            for _ in 0..exponent {
+                z = z.mul(self);
+            }
+            z
+        }
+    }
+
+    fn to_complex(&self) -> Complex {
+        Complex::new(self.real.to_f64().value(), self.imag.to_f64().value())
+    }
+}
+
+// degree of the polynomial Complex::powi(power) evaluates
+pub fn degree(power: u32) -> u32 {
+    if power == 0 {
+        0
+    } else {
+        power + 1
+    }
+}
+
+fn binomials(n: u32) -> Vec<f64> {
+    let mut row = vec![1.0];
+    for k in 1..=n {
+        let prev = row[k as usize - 1];
+        row.push(prev * (n - k + 1) as f64 / k as f64);
+    }
+    row
+}
+
+/// Orbit of a single point calculated in arbitrary precision.
+/// Every other pixel only iterates its (small) difference to this orbit in f64:
+/// (Z + dz)^d + (C + dc) - (Z^d + C) = sum_k binom(d, k) * Z^(d-k) * dz^k + dc
+/// For the Mandelbrot set the pixel offset goes into dc, for Julia sets into the starting dz.
+pub struct Reference {
+    orbit: Vec<Complex>,
+    // dZ/dC (dZ/dZ0 for julia sets) along the orbit if the options track derivatives, empty otherwise.
+    // Pixels start out with the derivative of the reference and follow their own z from there
+    derivatives: Vec<Complex>,
+    // smallest |Z| of the first m iterations for the minimum interior coloring, empty otherwise.
+    // The iterations the series skips barely differ from the reference's
+    minima: Vec<f64>,
+    binomials: Vec<f64>,
+    power: u32,
+    offset_is_c: bool,
+    radius: f64,
+    bailout: Bailout,
+}
+
+impl Reference {
+    // the precision of cx / cy is used for the whole orbit. Every iteration advances control,
+    // a cancelled orbit stops short
+    pub fn mandelbrot(cx: &FBig, cy: &FBig, options: &RenderOptions, control: &Control) -> Self {
+        let precision = cx.precision().max(cy.precision());
+        let c = BigComplex::new(cx.clone(), cy.clone());
+        let z = BigComplex::new(big_from_f64(0.0, precision), big_from_f64(0.0, precision));
+        Self::new(z, c, true, options, control)
+    }
+
+    // orbit starting at (zx, zy) under z^d + julia
+    pub fn julia(zx: &FBig, zy: &FBig, julia: Complex, options: &RenderOptions, control: &Control) -> Self {
+        let precision = zx.precision().max(zy.precision());
+        let c = BigComplex::new(big_from_f64(julia.real(), precision), big_from_f64(julia.imag(), precision));
+        let z = BigComplex::new(zx.clone(), zy.clone());
+        Self::new(z, c, false, options, control)
+    }
+
+    fn new(z: BigComplex, c: BigComplex, offset_is_c: bool, options: &RenderOptions, control: &Control) -> Self {
+        let precision = z.real.precision().max(c.real.precision());
+        let mut z = z;
+        let mut orbit = vec![z.to_complex()];
+        for _ in 0..options.tries {
+            if control.is_cancelled() {
+                break;
+            }
+            z = z.powi(options.power, precision).add(&c);
+            let small_z = z.to_complex();
+            orbit.push(small_z);
+            control.advance(1);
+            if options.escaped(small_z) {
+                break;
+            }
+        }
+        // an orbit that escaped or got cancelled is done early, the progress jumps to the pixels
+        control.advance(options.tries as u64 + 1 - orbit.len() as u64);
+        let mut derivatives = Vec::new();
+        if options.tracks_derivative() {
+            let mut dz = if offset_is_c { Complex::null() } else { Complex::new(1.0, 0.0) };
+            derivatives.push(dz);
+            for z in &orbit[..orbit.len() - 1] {
+                dz = derivative(*z, dz, options.power) + Complex::new(if offset_is_c { 1.0 } else { 0.0 }, 0.0);
+                derivatives.push(dz);
+            }
+        }
+        let mut minima = Vec::new();
+        if options.interior == Interior::Minimum {
+            minima.push(f64::INFINITY);
+            for z in &orbit[1..] {
+                minima.push(minima[minima.len() - 1].min(z.dist_from_origin()));
+            }
+        }
+        Reference {
+            orbit,
+            derivatives,
+            minima,
+            power: options.power,
+            binomials: binomials(degree(options.power)),
+            offset_is_c,
+            radius: options.radius,
+            bailout: options.bailout,
+        }
+    }
+
+    pub fn escaped(&self, z: Complex) -> bool {
+        self.bailout.escaped(z, self.radius)
+    }
+
+    // true if the pixel offset is added to c (Mandelbrot), false if it moves the starting point (Julia)
+    pub fn offset_is_c(&self) -> bool {
+        self.offset_is_c
+    }
+
+    pub fn orbit(&self) -> &[Complex] {
+        &self.orbit
+    }
+
+    // derivative of the reference after m iterations, null if derivatives aren't tracked
+    pub fn derivative(&self, m: usize) -> Complex {
+        self.derivatives.get(m).copied().unwrap_or_else(Complex::null)
+    }
+
+    // smallest |Z| of the first m iterations, infinite if the minimum isn't tracked
+    pub fn minimum(&self, m: usize) -> f64 {
+        self.minima.get(m).copied().unwrap_or(f64::INFINITY)
+    }
+
+    // interior point after all the tries, with the minimum if it's tracked. The cycle isn't searched for,
+    // the interior colorings that need it stay black
+    fn inside(&self, tries: u32, z: Complex, minimum: f64) -> Escape {
+        let interior = if self.minima.is_empty() { None } else { Some(minimum) };
+        Escape { interior, ..Escape::new(tries, z) }
+    }
+
+    // one step of the pixel's own derivative at its full value z
+    fn step_derivative(&self, z: Complex, dz: Complex) -> Complex {
+        if self.derivatives.is_empty() {
+            return dz;
+        }
+        let c = if self.offset_is_c { 1.0 } else { 0.0 };
+        derivative(z, dz, self.power) + Complex::new(c, 0.0)
+    }
+
+    // binom(d, k) for k = 0..=d
+    pub fn binomials(&self) -> &[f64] {
+        &self.binomials
+    }
+
+    // (starting dz, dc) for a pixel at offset from the reference
+    pub fn split(&self, offset: ComplexExp) -> (ComplexExp, ComplexExp) {
+        if self.offset_is_c {
+            (ComplexExp::null(), offset)
+        } else {
+            (offset, ComplexExp::null())
+        }
+    }
+
+    fn perturb(&self, z_ref: Complex, dz: Complex) -> Complex {
+        // horner scheme over dz, highest power first
+        let d = self.binomials.len() - 1;
+        let mut acc = Complex::null();
+        let mut z_pow = Complex::new(1.0, 0.0);
+        for k in (1..=d).rev() {
+            acc = acc * dz + z_pow * Complex::new(self.binomials[k], 0.0);
+            z_pow = z_pow * z_ref;
+        }
+        acc * dz
+    }
+
+    pub fn perturb_exp(&self, z_ref: Complex, dz: ComplexExp) -> ComplexExp {
+        let d = self.binomials.len() - 1;
+        let mut acc = ComplexExp::null();
+        let mut z_pow = Complex::new(1.0, 0.0);
+        for k in (1..=d).rev() {
+            acc = acc * dz + ComplexExp::from(z_pow * Complex::new(self.binomials[k], 0.0));
+            z_pow = z_pow * z_ref;
+        }
+        acc * dz
+    }
+
+    // same result as julia::mandelbrot / Julia::stable for the point reference + offset,
+    // renders go through series::Series which skips the start of the orbit
+    #[cfg(test)]
+    pub fn stable(&self, offset: Complex, tries: u32) -> Escape {
+        let (dz, dc) = self.split(ComplexExp::from(offset));
+        self.iterate(dz.to_complex(), dc.to_complex(), self.derivative(0), self.minimum(0), 0, 0, tries)
+    }
+
+    // for offsets below the f64 range
+    #[cfg(test)]
+    pub fn stable_exp(&self, offset: ComplexExp, tries: u32) -> Escape {
+        let (dz, dc) = self.split(offset);
+        self.iterate_exp(dz, dc, self.derivative(0), self.minimum(0), 0, 0, tries)
+    }
+
+    // iterates in FloatExp until dz is big enough for f64, then continues with iterate.
+    // A dc below the f64 range keeps the whole orbit in FloatExp: after a rebase near a minibrot
+    // dz starts from scratch and dc is what tells the pixel apart from the reference again.
+    // der is the derivative of the pixel so far, minimum its smallest |z|
+    pub fn iterate_exp(&self, dz: ComplexExp, dc: ComplexExp, der: Complex, minimum: f64, m: usize, start: u32, tries: u32) -> Escape {
+        let dc_fits = !self.offset_is_c || dc.exponent() > F64_SAFE_EXPONENT;
+        let mut dz = dz;
+        let mut der = der;
+        let mut minimum = minimum;
+        let track_minimum = !self.minima.is_empty();
+        let mut m = m;
+        for i in start..tries {
+            der = self.step_derivative(self.orbit[m] + dz.to_complex(), der);
+            dz = self.perturb_exp(self.orbit[m], dz) + dc;
+            m += 1;
+            let z = ComplexExp::from(self.orbit[m]) + dz;
+            let small_z = z.to_complex();
+            if self.escaped(small_z) {
+                return Escape::new(i, small_z).with_derivative(der);
+            }
+            if track_minimum {
+                minimum = minimum.min(small_z.dist_from_origin());
+            }
+            // same glitch as in iterate
+            if m + 1 == self.orbit.len() || z.dist_from_origin() < dz.dist_from_origin() {
+                dz = z - ComplexExp::from(self.orbit[0]);
+                m = 0;
+            } else if dc_fits && dz.exponent() > F64_SAFE_EXPONENT {
+                return self.iterate(dz.to_complex(), dc.to_complex(), der, minimum, m, i + 1, tries);
+            }
+        }
+        self.inside(tries, self.orbit[m] + dz.to_complex(), minimum)
+    }
+
+    // iterations start..tries with dz relative to orbit[m]
+    pub fn iterate(&self, dz: Complex, dc: Complex, der: Complex, minimum: f64, m: usize, start: u32, tries: u32) -> Escape {
+        let mut dz = dz;
+        let mut der = der;
+        let mut minimum = minimum;
+        let track_minimum = !self.minima.is_empty();
+        let mut m = m;
+        for i in start..tries {
+            der = self.step_derivative(self.orbit[m] + dz, der);
+            dz = self.perturb(self.orbit[m], dz) + dc;
+            m += 1;
+            let z = self.orbit[m] + dz;
+            if self.escaped(z) {
+                return Escape::new(i, z).with_derivative(der);
+            }
+            if track_minimum {
+                minimum = minimum.min(z.dist_from_origin());
+            }
+            // glitch: dz got as big as the value itself and its precision is lost soon,
+            // or the reference escaped already -> rebase onto the start of the orbit
+            if m + 1 == self.orbit.len() || z.dist_from_origin() < dz.dist_from_origin() {
+                dz = z - self.orbit[0];
+                m = 0;
+            }
+        }
+        self.inside(tries, self.orbit[m] + dz, minimum)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Reference, Interior, binomials, big_from_f64};
+    use dashu_float::FBig;
+    use super::super::{mandelbrot, Control, Julia, Fractal, RenderOptions, Bailout, complex::Complex, floatexp::{FloatExp, ComplexExp}};
+
+    fn reference(cx: f64, cy: f64, tries: u32, power: u32) -> Reference {
+        Reference::mandelbrot(&big_from_f64(cx, 200), &big_from_f64(cy, 200), &RenderOptions::new(tries, power), &Control::default())
+    }
+
+    #[test]
+    fn binomial_rows() {
+        assert_eq!(binomials(0), vec![1.0]);
+        assert_eq!(binomials(2), vec![1.0, 2.0, 1.0]);
+        assert_eq!(binomials(4), vec![1.0, 4.0, 6.0, 4.0, 1.0]);
+    }
+
+    #[test]
+    fn delta_matches_direct_iteration() {
+        // shallow enough that plain f64 is still exact
+        let (cx, cy) = (-0.7436, 0.1318);
+        let span = 1e-4;
+        for power in 1..4 {
+            let reference = reference(cx, cy, 500, power);
+            let mut mismatches = 0;
+            for i in 0..20 {
+                for j in 0..20 {
+                    let dx = (i as f64 / 19.0 - 0.5) * span;
+                    let dy = (j as f64 / 19.0 - 0.5) * span;
+                    let direct = mandelbrot(cx + dx, cy + dy, &RenderOptions::new(500, power));
+                    if reference.stable(Complex::new(dx, dy), 500).iterations != direct.iterations {
+                        mismatches += 1;
+                    }
+                }
+            }
+            // chaotic boundary pixels may round differently
+            assert!(mismatches <= 4, "{} mismatches for power {}", mismatches, power);
+        }
+    }
+
+    #[test]
+    fn derivative_matches_direct_iteration() {
+        let (cx, cy) = (-0.7436, 0.1318);
+        let mut options = RenderOptions::new(500, 1);
+        options.derivative = true;
+        let reference = Reference::mandelbrot(&big_from_f64(cx, 64), &big_from_f64(cy, 64), &options, &Control::default());
+        let offset = Complex::new(3e-5, -2e-5);
+        let direct = mandelbrot(cx + offset.real(), cy + offset.imag(), &options);
+        let perturbed = reference.stable(offset, 500);
+        assert_eq!(perturbed.iterations, direct.iterations);
+        let error = (perturbed.derivative - direct.derivative).dist_from_origin() / direct.derivative.dist_from_origin();
+        assert!(error < 1e-6, "relative error {}", error);
+    }
+
+    #[test]
+    fn interior_minimum_matches_direct_iteration() {
+        // inside the main cardioid, the orbit creeps towards the fixed point
+        let (cx, cy) = (-0.1, 0.2);
+        let mut options = RenderOptions::new(300, 1);
+        options.interior = Interior::Minimum;
+        options.periodicity = false;
+        let reference = Reference::mandelbrot(&big_from_f64(cx, 64), &big_from_f64(cy, 64), &options, &Control::default());
+        let offset = Complex::new(2e-3, -1e-3);
+        let direct = mandelbrot(cx + offset.real(), cy + offset.imag(), &options);
+        let perturbed = reference.stable(offset, 300);
+        assert_eq!(perturbed.iterations, 300);
+        assert!((perturbed.interior.unwrap() - direct.interior.unwrap()).abs() < 1e-12);
+        // the cycle isn't searched for past the f64 zoom
+        options.interior = Interior::Period;
+        let reference = Reference::mandelbrot(&big_from_f64(cx, 64), &big_from_f64(cy, 64), &options, &Control::default());
+        assert_eq!((reference.stable(offset, 300).period, reference.stable(offset, 300).interior), (None, None));
+    }
+
+    #[test]
+    fn rebases_escaped_reference() {
+        // reference escapes immediately, the pixels next to it don't
+        let reference = reference(2.0, 0.0, 100, 1);
+        assert_eq!(reference.stable(Complex::new(-2.0, 0.0), 100).iterations, 100);
+        let options = RenderOptions::new(100, 1);
+        assert_eq!(reference.stable(Complex::new(-2.25, 0.0), 100).iterations, mandelbrot(-0.25, 0.0, &options).iterations);
+    }
+
+    #[test]
+    fn resolves_beyond_f64() {
+        // c = i sits on the boundary, so there is detail at every scale,
+        // far past the point where plain f64 collapses to a single value
+        let (cx, cy) = (0.0, 1.0);
+        let span = 1e-30;
+        let reference = reference(cx, cy, 5000, 1);
+        let mut counts = Vec::new();
+        for i in 0..16 {
+            let dc = Complex::new((i as f64 / 15.0 - 0.5) * span, 0.0);
+            counts.push(reference.stable(dc, 5000).iterations);
+        }
+        counts.dedup();
+        assert!(counts.len() > 1);
+    }
+
+    #[test]
+    fn floatexp_matches_f64() {
+        let (cx, cy) = (-0.7436, 0.1318);
+        let span = 1e-4;
+        let reference = reference(cx, cy, 500, 1);
+        for i in 0..20 {
+            for j in 0..20 {
+                let offset = Complex::new((i as f64 / 19.0 - 0.5) * span, (j as f64 / 19.0 - 0.5) * span);
+                assert_eq!(reference.stable(offset, 500).iterations, reference.stable_exp(ComplexExp::from(offset), 500).iterations);
+            }
+        }
+    }
+
+    #[test]
+    fn resolves_beyond_floatexp_threshold() {
+        let reference = reference(0.0, 1.0, 20000, 1);
+        let mut counts = Vec::new();
+        for i in 0..16 {
+            // 1e-400 isn't an f64 anymore
+            let x = FloatExp::new(i as f64 / 15.0 - 0.5) * FloatExp::new(1e-200) * FloatExp::new(1e-200);
+            counts.push(reference.stable_exp(ComplexExp::new(x, FloatExp::zero()), 20000).iterations);
+        }
+        assert!(counts.iter().all(|i| *i < 20000));
+        counts.dedup();
+        assert!(counts.len() > 1);
+    }
+
+    #[test]
+    fn julia_delta_matches_direct_iteration() {
+        let jul = Julia::new(-0.8, 0.156);
+        let (zx, zy) = (0.1, 0.2);
+        let span = 1e-3;
+        for power in 1..3 {
+            let reference = Reference::julia(&big_from_f64(zx, 200), &big_from_f64(zy, 200), jul, &RenderOptions::new(500, power), &Control::default());
+            let mut mismatches = 0;
+            for i in 0..20 {
+                for j in 0..20 {
+                    let dx = (i as f64 / 19.0 - 0.5) * span;
+                    let dy = (j as f64 / 19.0 - 0.5) * span;
+                    let direct = jul.stable_cords(zx + dx, zy + dy, &RenderOptions::new(500, power));
+                    if reference.stable(Complex::new(dx, dy), 500).iterations != direct.iterations {
+                        mismatches += 1;
+                    }
+                }
+            }
+            assert!(mismatches <= 4, "{} mismatches for power {}", mismatches, power);
+        }
+    }
+
+    #[test]
+    fn bailout_matches_direct_iteration() {
+        let mut options = RenderOptions::new(300, 1);
+        options.radius = 50.0;
+        options.bailout = Bailout::Manhattan;
+        let (cx, cy) = (-0.7436, 0.1318);
+        let span = 1e-4;
+        let reference = Reference::mandelbrot(&big_from_f64(cx, 200), &big_from_f64(cy, 200), &options, &Control::default());
+        let mut mismatches = 0;
+        for i in 0..20 {
+            for j in 0..20 {
+                let dx = (i as f64 / 19.0 - 0.5) * span;
+                let dy = (j as f64 / 19.0 - 0.5) * span;
+                let direct = mandelbrot(cx + dx, cy + dy, &options);
+                if reference.stable(Complex::new(dx, dy), 300).iterations != direct.iterations {
+                    mismatches += 1;
+                }
+            }
+        }
+        assert!(mismatches <= 4);
+    }
+
+    // nucleus of the period `period` component next to guess, by newton on the real axis
+    fn nucleus(guess: FBig, period: u32) -> FBig {
+        let precision = guess.precision();
+        let (one, two) = (big_from_f64(1.0, precision), big_from_f64(2.0, precision));
+        let mut c = guess;
+        for _ in 0..20 {
+            let mut z = big_from_f64(0.0, precision);
+            let mut dz = big_from_f64(0.0, precision);
+            for _ in 0..period {
+                dz = &two * &z * &dz + &one;
+                z = &z * &z + &c;
+            }
+            c = &c - z / dz;
+        }
+        c
+    }
+
+    // iterations until the point escapes, straight from its own orbit
+    fn direct(cx: &FBig, cy: &FBig, tries: u32) -> u32 {
+        let reference = Reference::mandelbrot(cx, cy, &RenderOptions::new(tries, 1), &Control::default());
+        let orbit = reference.orbit();
+        if reference.escaped(orbit[orbit.len() - 1]) {
+            orbit.len() as u32 - 2
+        } else {
+            tries
+        }
+    }
+
+    #[test]
+    fn rebases_below_f64() {
+        // period 267 minibrot on the antenna, about 2e-320 wide. Pixels closer to its nucleus than
+        // to the reference glitch while dc is far below f64 and have to keep it after the rebase
+        let precision = 1200;
+        let guess = big_from_f64(-2.0, precision) + big_from_f64(2.6325e-160, precision);
+        let nucleus = nucleus(guess, 267);
+        // 2^-1064, about 1.2e-320
+        let unit = big_from_f64(2f64.powi(-532), precision) * big_from_f64(2f64.powi(-532), precision);
+        let at = |x: f64, y: f64| (&nucleus + big_from_f64(x, precision) * &unit, big_from_f64(y, precision) * &unit);
+        let (rx, ry) = (0.75, 0.5);
+        let (cx, cy) = at(rx, ry);
+        let tries = 6000;
+        let reference = Reference::mandelbrot(&cx, &cy, &RenderOptions::new(tries, 1), &Control::default());
+        for x in [-3.0, -2.0, 2.0, 3.0] {
+            for y in [1.0, 2.0, 3.0] {
+                let offset = ComplexExp::new(FloatExp::from_parts(x - rx, -1064), FloatExp::from_parts(y - ry, -1064));
+                let (px, py) = at(x, y);
+                assert_eq!(reference.stable_exp(offset, tries).iterations, direct(&px, &py, tries), "pixel at {} {}", x, y);
+            }
+        }
+    }
+}
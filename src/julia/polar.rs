@@ -3,6 +3,7 @@ use std::iter::Iterator;
 
 pub type Iter<T> = Box<dyn Iterator<Item = T>>;
 
+#[derive(Clone, Copy)]
 pub struct PolarSpace {
     anchor: (f64, f64)
 }
@@ -20,8 +21,9 @@ impl PolarSpace {
     pub fn rectangular_to_polar(self, x: f64, y: f64) -> (f64, f64) {
         let x = x - self.anchor.0;
         let y = y - self.anchor.1;
-        let r = (x.powi(2) + y.powi(2)).sqrt();
-        let a = (y / x).atan();
+        let r = x.hypot(y);
+        // atan2 keeps the quadrant, the angle of (-1, 0) is PI and not 0
+        let a = y.atan2(x);
         (r,a)
     }
 }
@@ -87,6 +89,30 @@ pub fn rectangular_radial_path(angle: f64, r_min: f64, r_max: f64, slices: u32)
     )
 }
 
+// exponents from start to end with the modulus and the angle changing evenly, the angle turns
+// the short way round so a morph between multibrots doesn't spin. Both ends are included
+pub fn exponent_path(start: (f64, f64), end: (f64, f64), slices: u32) -> Iter<(f64, f64)> {
+    let space = PolarSpace::new(0.0, 0.0);
+    let (r_start, a_start) = space.rectangular_to_polar(start.0, start.1);
+    let (r_end, a_end) = space.rectangular_to_polar(end.0, end.1);
+    // the difference of the angles taken into [-PI, PI), the short way round
+    let turn = rotate(a_end - a_start, PI) - PI;
+    let steps = slices.saturating_sub(1).max(1) as f64;
+    Box::new(
+        (0..slices).map(move |i| {
+            // exactly, not after a round trip through the polar form
+            if i == 0 {
+                return start;
+            }
+            if i + 1 == slices {
+                return end;
+            }
+            let t = i as f64 / steps;
+            space.polar_to_rectangular(r_start + t * (r_end - r_start), rotate(a_start, t * turn))
+        })
+    )
+}
+
 fn radial_path(angle: f64, r_min: f64, r_max: f64, slices: u32) -> Iter<(f64, f64)> {
     let dif = r_max - r_min;
     Box::new(
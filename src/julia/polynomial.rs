@@ -0,0 +1,206 @@
+use super::complex::Complex;
+use super::pow;
+
+// Durand-Kerner iterations before the roots are taken as they are, multiple roots converge slowly
+const ROOT_ITERATIONS: usize = 1000;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PolynomialError {
+    // degree after dropping leading zeros, below 2 nothing escapes in an interesting way
+    Degree(usize),
+    // index of the parameter coefficient past the degree
+    Parameter(usize),
+}
+
+/// p(z) = a_0 + a_1 z + ... + a_n z^n. In the parameter plane the coefficient a_parameter is the pixel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polynomial {
+    // lowest power first, the last one isn't zero
+    coefficients: Vec<Complex>,
+    parameter: usize,
+}
+
+impl Polynomial {
+    pub fn new(coefficients: Vec<Complex>, parameter: usize) -> Result<Self, PolynomialError> {
+        let mut coefficients = coefficients;
+        while coefficients.last() == Some(&Complex::null()) {
+            coefficients.pop();
+        }
+        let degree = coefficients.len().saturating_sub(1);
+        if degree < 2 {
+            return Err(PolynomialError::Degree(degree));
+        }
+        if parameter > degree {
+            return Err(PolynomialError::Parameter(parameter));
+        }
+        Ok(Polynomial { coefficients, parameter })
+    }
+
+    // (z - r_1) ... (z - r_n)
+    pub fn from_roots(roots: &[Complex]) -> Result<Self, PolynomialError> {
+        let mut coefficients = vec![Complex::new(1.0, 0.0)];
+        for &root in roots {
+            // multiply by z - root
+            let mut product = vec![Complex::null(); coefficients.len() + 1];
+            for (k, &a) in coefficients.iter().enumerate() {
+                product[k + 1] += a;
+                product[k] -= a * root;
+            }
+            coefficients = product;
+        }
+        Polynomial::new(coefficients, 0)
+    }
+
+    pub fn degree(&self) -> usize {
+        self.coefficients.len() - 1
+    }
+
+    pub fn parameter(&self) -> usize {
+        self.parameter
+    }
+
+    // the same polynomial with c for the parameter coefficient
+    pub fn with_parameter(&self, c: Complex) -> Self {
+        let mut coefficients = self.coefficients.clone();
+        coefficients[self.parameter] = c;
+        Polynomial { coefficients, parameter: self.parameter }
+    }
+
+    // a_k, c replaces the parameter coefficient if there is one
+    pub fn coefficient(&self, k: usize, c: Option<Complex>) -> Complex {
+        match c {
+            Some(c) if k == self.parameter => c,
+            _ => self.coefficients[k],
+        }
+    }
+
+    // p(z) and p'(z) by Horner's scheme
+    pub fn evaluate(&self, z: Complex, c: Option<Complex>) -> (Complex, Complex) {
+        let mut p = Complex::null();
+        let mut dp = Complex::null();
+        for k in (0..self.coefficients.len()).rev() {
+            dp = dp * z + p;
+            p = p * z + self.coefficient(k, c);
+        }
+        (p, dp)
+    }
+
+    // p(z), p'(z) and p''(z)
+    pub fn derivatives(&self, z: Complex) -> (Complex, Complex, Complex) {
+        let (mut p, mut dp, mut ddp) = (Complex::null(), Complex::null(), Complex::null());
+        for &a in self.coefficients.iter().rev() {
+            ddp = ddp * z + dp * Complex::new(2.0, 0.0);
+            dp = dp * z + p;
+            p = p * z + a;
+        }
+        (p, dp, ddp)
+    }
+
+    // all roots with their multiplicity by the Durand-Kerner iteration
+    pub fn roots(&self) -> Vec<Complex> {
+        let n = self.degree();
+        let leading = self.coefficients[n];
+        let monic: Vec<Complex> = self.coefficients.iter().map(|&a| a / leading).collect();
+        let value = |z: Complex| monic.iter().rev().fold(Complex::null(), |p, &a| p * z + a);
+        // powers of a number that's neither real nor a root of unity keep the guesses apart
+        let seed = Complex::new(0.4, 0.9);
+        let mut roots: Vec<Complex> = (0..n).map(|k| pow(seed, k as u32)).collect();
+        for _ in 0..ROOT_ITERATIONS {
+            let mut change: f64 = 0.0;
+            for i in 0..n {
+                let denominator = (0..n).filter(|&j| j != i).fold(Complex::new(1.0, 0.0), |d, j| d * (roots[i] - roots[j]));
+                if denominator == Complex::null() {
+                    continue;
+                }
+                let step = value(roots[i]) / denominator;
+                roots[i] -= step;
+                change = change.max(step.dist_from_origin());
+            }
+            if change < 1e-15 {
+                break;
+            }
+        }
+        roots
+    }
+
+    // every orbit that gets further out than this escapes:
+    // for |z| >= max(1, (2 + |a_0| + ... + |a_n-1|) / |a_n|), |p(z)| >= |z|^(n-1) (|a_n| |z| - sum) >= 2 |z|
+    pub fn radius(&self, c: Option<Complex>) -> f64 {
+        let n = self.degree();
+        let leading = self.coefficient(n, c).dist_from_origin();
+        if leading == 0.0 {
+            return f64::INFINITY;
+        }
+        let sum: f64 = (0..n).map(|k| self.coefficient(k, c).dist_from_origin()).sum();
+        ((2.0 + sum) / leading).max(1.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Polynomial, PolynomialError};
+    use super::super::complex::Complex;
+
+    fn real(coefficients: &[f64], parameter: usize) -> Result<Polynomial, PolynomialError> {
+        Polynomial::new(coefficients.iter().map(|&a| Complex::new(a, 0.0)).collect(), parameter)
+    }
+
+    #[test]
+    fn evaluates_with_derivative() {
+        // 1 - 2z + 3z^3
+        let p = real(&[1.0, -2.0, 0.0, 3.0, 0.0], 0).unwrap();
+        assert_eq!(p.degree(), 3);
+        let z = Complex::new(0.5, -1.0);
+        let (value, derivative) = p.evaluate(z, None);
+        let expected = Complex::new(1.0, 0.0) - Complex::new(2.0, 0.0) * z + Complex::new(3.0, 0.0) * z * z * z;
+        assert!((value - expected).dist_from_origin() < 1e-12);
+        assert!((derivative - (Complex::new(9.0, 0.0) * z * z - Complex::new(2.0, 0.0))).dist_from_origin() < 1e-12);
+        // the parameter stands in for a_0
+        let (value, _) = p.evaluate(z, Some(Complex::new(0.0, 1.0)));
+        assert!((value - expected - Complex::new(-1.0, 1.0)).dist_from_origin() < 1e-12);
+        assert_eq!(p.with_parameter(Complex::new(0.0, 1.0)).evaluate(z, None).0, value);
+
+        assert_eq!(real(&[1.0, 2.0, 0.0], 0), Err(PolynomialError::Degree(1)));
+        assert_eq!(real(&[1.0, 0.0, 1.0], 3), Err(PolynomialError::Parameter(3)));
+    }
+
+    #[test]
+    fn roots_and_coefficients() {
+        // z^3 - 1
+        let p = real(&[-1.0, 0.0, 0.0, 1.0], 0).unwrap();
+        let mut roots = p.roots();
+        roots.sort_by(|a, b| a.imag().partial_cmp(&b.imag()).unwrap());
+        let half = 0.75f64.sqrt();
+        for (root, expected) in roots.iter().zip([Complex::new(-0.5, -half), Complex::new(1.0, 0.0), Complex::new(-0.5, half)]) {
+            assert!((*root - expected).dist_from_origin() < 1e-12, "{:?}", root);
+        }
+        let q = Polynomial::from_roots(&roots).unwrap();
+        assert!(q.coefficients.iter().zip(&p.coefficients).all(|(a, b)| (*a - *b).dist_from_origin() < 1e-12));
+        // (z - 2)^2 z = z^3 - 4z^2 + 4z
+        let p = Polynomial::from_roots(&[Complex::new(2.0, 0.0), Complex::new(2.0, 0.0), Complex::null()]).unwrap();
+        assert_eq!(p, real(&[0.0, 4.0, -4.0, 1.0], 0).unwrap());
+        let (value, first, second) = p.derivatives(Complex::new(1.0, 0.0));
+        assert_eq!((value, first, second), (Complex::new(1.0, 0.0), Complex::new(-1.0, 0.0), Complex::new(-2.0, 0.0)));
+    }
+
+    #[test]
+    fn radius_bounds_the_orbits() {
+        // z^2 + c: every orbit past max(1, 2 + |c|) escapes
+        let p = real(&[0.0, 0.0, 1.0], 0).unwrap();
+        assert_eq!(p.radius(Some(Complex::new(-2.0, 0.0))), 4.0);
+        let p = real(&[0.3, -1.5, 0.5, 0.25], 0).unwrap();
+        let radius = p.radius(None);
+        for i in 0..16 {
+            let angle = i as f64 * std::f64::consts::PI / 8.0;
+            let mut z = Complex::new(radius * 1.001 * angle.cos(), radius * 1.001 * angle.sin());
+            for _ in 0..4 {
+                let next = p.evaluate(z, None).0;
+                assert!(next.dist_from_origin() >= 2.0 * z.dist_from_origin());
+                z = next;
+            }
+        }
+        // c in front of the highest power can make the polynomial drop a degree
+        let p = real(&[0.0, 0.0, 1.0], 2).unwrap();
+        assert_eq!(p.radius(Some(Complex::null())), f64::INFINITY);
+    }
+}
@@ -0,0 +1,184 @@
+use super::complex::Complex;
+use super::floatexp::{FloatExp, ComplexExp};
+use super::perturbation::Reference;
+use super::{Escape, Control};
+
+// biggest error of the approximation relative to the real offset that's still accepted
+const TOLERANCE: f64 = 1e-9;
+
+/// Series approximation of the perturbation offset: dz_n ~ a_n * dc + b_n * dc^2 + c_n * dc^3
+/// (dc is the starting dz for Julia sets). As long as it holds for the whole frame, the first
+/// `skipped` iterations are the same polynomial for every pixel and don't have to be iterated.
+pub struct Series {
+    skipped: u32,
+    a: ComplexExp,
+    b: ComplexExp,
+    c: ComplexExp,
+}
+
+impl Series {
+    // finds how far the series holds for a frame of the given span centered at the reference, stops where it is when cancelled
+    pub fn new(reference: &Reference, x_dif: FloatExp, y_dif: FloatExp, tries: u32, control: &Control) -> Self {
+        let one = ComplexExp::from(Complex::new(1.0, 0.0));
+        let mut series = Series {
+            skipped: 0,
+            a: if reference.offset_is_c() { ComplexExp::null() } else { one },
+            b: ComplexExp::null(),
+            c: ComplexExp::null(),
+        };
+        let binomials = reference.binomials();
+        let d = binomials.len() - 1;
+        if d < 2 {
+            // z^0 doesn't depend on z at all
+            return series;
+        }
+        let orbit = reference.orbit();
+
+        // corners and edge midpoints, |z| over the frame is biggest on its border
+        let half_x = x_dif * FloatExp::new(0.5);
+        let half_y = y_dif * FloatExp::new(0.5);
+        let mut probes = Vec::new();
+        for (rx, ry) in &[(-1.0, -1.0), (0.0, -1.0), (1.0, -1.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0), (-1.0, 1.0), (-1.0, 0.0)] {
+            let offset = ComplexExp::new(half_x * FloatExp::new(*rx), half_y * FloatExp::new(*ry));
+            let (dz, dc) = reference.split(offset);
+            probes.push((offset, dz, dc));
+        }
+
+        let new_a_term = if reference.offset_is_c() { one } else { ComplexExp::null() };
+        let limit = (tries as usize).min(orbit.len().saturating_sub(2));
+        for n in 0..limit {
+            if control.is_cancelled() {
+                break;
+            }
+            // d * Z^(d-1), binom(d, 2) * Z^(d-2), binom(d, 3) * Z^(d-3)
+            let z = orbit[n];
+            let mut z_pows = vec![Complex::new(1.0, 0.0)];
+            for k in 1..d {
+                z_pows.push(z_pows[k - 1] * z);
+            }
+            let t1 = ComplexExp::from(z_pows[d - 1] * Complex::new(binomials[1], 0.0));
+            let t2 = ComplexExp::from(z_pows[d - 2] * Complex::new(binomials[2], 0.0));
+            let t3 = if d >= 3 {
+                ComplexExp::from(z_pows[d - 3] * Complex::new(binomials[3], 0.0))
+            } else {
+                ComplexExp::null()
+            };
+            let (a, b, c) = (series.a, series.b, series.c);
+            let next = Series {
+                skipped: n as u32 + 1,
+                a: t1 * a + new_a_term,
+                b: t1 * b + t2 * a * a,
+                c: t1 * c + t2 * (a * b + a * b) + t3 * a * a * a,
+            };
+
+            let z_next = orbit[n + 1];
+            let mut valid = true;
+            for (offset, dz, dc) in probes.iter_mut() {
+                *dz = reference.perturb_exp(z, *dz) + *dc;
+                let error = (next.approximate(*offset) - *dz).dist_from_origin();
+                let small_dz = dz.to_complex();
+                let full = z_next + small_dz;
+                // a probe escaping or needing a rebase would be wrong for the pixels around it too
+                if error > FloatExp::new(TOLERANCE) * dz.dist_from_origin()
+                    || reference.escaped(full)
+                    || full.dist_from_origin() < small_dz.dist_from_origin() {
+                    valid = false;
+                }
+            }
+            if !valid {
+                break;
+            }
+            series = next;
+        }
+        series
+    }
+
+    pub fn skipped(&self) -> u32 {
+        self.skipped
+    }
+
+    fn approximate(&self, offset: ComplexExp) -> ComplexExp {
+        offset * (self.a + offset * (self.b + offset * self.c))
+    }
+
+    // same as Reference::stable, starting after the skipped iterations
+    pub fn stable(&self, reference: &Reference, offset: Complex, tries: u32) -> Escape {
+        let offset = ComplexExp::from(offset);
+        let (_, dc) = reference.split(offset);
+        let dz = self.approximate(offset);
+        let skipped = self.skipped as usize;
+        // the derivative barely differs from the reference's over the skipped iterations
+        reference.iterate(dz.to_complex(), dc.to_complex(), reference.derivative(skipped), reference.minimum(skipped), skipped, self.skipped, tries)
+    }
+
+    // same as Reference::stable_exp, starting after the skipped iterations
+    pub fn stable_exp(&self, reference: &Reference, offset: ComplexExp, tries: u32) -> Escape {
+        let (_, dc) = reference.split(offset);
+        let dz = self.approximate(offset);
+        let skipped = self.skipped as usize;
+        reference.iterate_exp(dz, dc, reference.derivative(skipped), reference.minimum(skipped), skipped, self.skipped, tries)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Series;
+    use super::super::perturbation::{Reference, big_from_f64};
+    use super::super::complex::Complex;
+    use super::super::{RenderOptions, Control};
+    use super::super::floatexp::{FloatExp, ComplexExp};
+
+    fn compare(reference: &Reference, span: f64, tries: u32) -> (u32, u32) {
+        let series = Series::new(reference, FloatExp::new(span), FloatExp::new(span), tries, &Control::default());
+        let mut mismatches = 0;
+        for i in 0..16 {
+            for j in 0..16 {
+                let offset = Complex::new((i as f64 / 15.0 - 0.5) * span, (j as f64 / 15.0 - 0.5) * span);
+                if series.stable(reference, offset, tries).iterations != reference.stable(offset, tries).iterations {
+                    mismatches += 1;
+                }
+            }
+        }
+        (series.skipped(), mismatches)
+    }
+
+    #[test]
+    fn skips_iterations_in_deep_zoom() {
+        // close to a mini brot, where every pixel shares a long prefix
+        for (power, cx) in &[(1, -1.7685736562), (2, 0.3)] {
+            let (cx, cy) = (big_from_f64(*cx, 300), big_from_f64(0.0, 300));
+            let reference = Reference::mandelbrot(&cx, &cy, &RenderOptions::new(3000, *power), &Control::default());
+            let (skipped, mismatches) = compare(&reference, 1e-20, 3000);
+            assert!(skipped > 10, "skipped only {} for power {}", skipped, power);
+            assert!(mismatches <= 3, "{} mismatches for power {}", mismatches, power);
+        }
+    }
+
+    #[test]
+    fn julia_series() {
+        let jul = Complex::new(-0.8, 0.156);
+        let reference = Reference::julia(&big_from_f64(0.1, 300), &big_from_f64(0.2, 300), jul, &RenderOptions::new(1000, 1), &Control::default());
+        let (skipped, mismatches) = compare(&reference, 1e-12, 1000);
+        assert!(skipped > 5);
+        assert!(mismatches <= 3);
+    }
+
+    #[test]
+    fn never_skips_escaping_pixels() {
+        // the frame contains pixels escaping after a few iterations
+        let reference = Reference::mandelbrot(&big_from_f64(-0.75, 100), &big_from_f64(0.1, 100), &RenderOptions::new(500, 1), &Control::default());
+        let (skipped, mismatches) = compare(&reference, 0.5, 500);
+        assert!(skipped < 3);
+        assert_eq!(mismatches, 0);
+    }
+
+    #[test]
+    fn floatexp_offsets() {
+        let (cx, cy) = (big_from_f64(-1.7685736562, 300), big_from_f64(0.0, 300));
+        let reference = Reference::mandelbrot(&cx, &cy, &RenderOptions::new(3000, 1), &Control::default());
+        let span = 1e-20;
+        let series = Series::new(&reference, FloatExp::new(span), FloatExp::new(span), 3000, &Control::default());
+        let offset = Complex::new(0.3 * span, -0.2 * span);
+        assert_eq!(series.stable(&reference, offset, 3000).iterations, series.stable_exp(&reference, ComplexExp::from(offset), 3000).iterations);
+    }
+}
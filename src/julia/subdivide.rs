@@ -0,0 +1,186 @@
+use std::sync::{mpsc, Arc};
+use threadpool::ThreadPool;
+
+use super::{Escape, RenderOptions, Control, slice_cord, WORKERS};
+use super::complex::Complex;
+
+// the frame is split into tiles of this size, every tile gets subdivided by one job
+const TILE: u32 = 64;
+
+// rectangles with less pixels across get computed pixel by pixel
+const MIN_SIZE: u32 = 4;
+
+/// Mariani-Silver rendering: computes the border of a rectangle, fills it if the whole border
+/// escaped in the same iteration and splits it into four smaller rectangles otherwise.
+/// Returns the escapes as columns just like the brute force renderer. Filled pixels are copies of the
+/// corner, if exact only the interior gets filled so every escaping pixel keeps its own z.
+pub fn escapes<F>(x_min: f64, x_max: f64, y_min: f64, y_max: f64, x_range: u32, y_range: u32, options: &RenderOptions, control: Arc<Control>, exact: bool, stable: F) -> Vec<Vec<Escape>>
+where F: Fn(f64, f64, &RenderOptions) -> Escape + Send + Sync + 'static {
+    let stable = Arc::new(stable);
+    let options = Arc::new(options.clone());
+    let pool = ThreadPool::new(WORKERS);
+    let (tx, rx) = mpsc::channel();
+
+    let mut tiles = 0;
+    for x0 in (0..x_range).step_by(TILE as usize) {
+        for y0 in (0..y_range).step_by(TILE as usize) {
+            let (width, height) = (TILE.min(x_range - x0), TILE.min(y_range - y0));
+            let tx = tx.clone();
+            let stable = stable.clone();
+            let options = options.clone();
+            let control = control.clone();
+            tiles += 1;
+            pool.execute(move || {
+                let pixel = |x: u32, y: u32| {
+                    if control.is_cancelled() {
+                        return Escape::new(options.tries, Complex::null());
+                    }
+                    let cord_x = slice_cord(x_min, x_max, x_range, x0 + x);
+                    let cord_y = slice_cord(y_min, y_max, y_range, y0 + y);
+                    stable(cord_x, cord_y, &options)
+                };
+                let mut tile = Tile::new(width, height);
+                tile.subdivide(0, 0, width - 1, height - 1, &pixel, &options, exact, &control);
+                if !control.is_cancelled() {
+                    control.advance((width * height) as u64);
+                }
+                tx.send((x0, y0, tile)).unwrap();
+            });
+        }
+    }
+
+    // pixels a cancelled render didn't get to stay black like the interior
+    let mut columns = vec![vec![Escape::new(options.tries, Complex::null()); y_range as usize]; x_range as usize];
+    for _ in 0..tiles {
+        let (x0, y0, tile) = rx.recv().unwrap();
+        // pixels are only missing if the render got cancelled
+        for (i, escape) in tile.pixels.into_iter().enumerate() {
+            if let Some(escape) = escape {
+                let (x, y) = (i as u32 % tile.width, i as u32 / tile.width);
+                columns[(x0 + x) as usize][(y0 + y) as usize] = escape;
+            }
+        }
+    }
+    pool.join();
+    columns
+}
+
+struct Tile {
+    width: u32,
+    pixels: Vec<Option<Escape>>,
+}
+
+impl Tile {
+    fn new(width: u32, height: u32) -> Self {
+        Tile { width, pixels: vec![None; (width * height) as usize] }
+    }
+
+    // computes the pixel unless it's known already
+    fn get<P>(&mut self, x: u32, y: u32, pixel: &P) -> Escape
+    where P: Fn(u32, u32) -> Escape {
+        let i = (y * self.width + x) as usize;
+        *self.pixels[i].get_or_insert_with(|| pixel(x, y))
+    }
+
+    fn set(&mut self, x: u32, y: u32, escape: Escape) {
+        self.pixels[(y * self.width + x) as usize] = Some(escape);
+    }
+
+    // handles the rectangle with the corners (x0, y0) and (x1, y1), both inclusive
+    fn subdivide<P>(&mut self, x0: u32, y0: u32, x1: u32, y1: u32, pixel: &P, options: &RenderOptions, exact: bool, control: &Control)
+    where P: Fn(u32, u32) -> Escape {
+        if control.is_cancelled() {
+            return;
+        }
+        let first = self.get(x0, y0, pixel);
+        let mut uniform = true;
+        for x in x0..=x1 {
+            uniform &= same_color(first, self.get(x, y0, pixel), options, exact);
+            uniform &= same_color(first, self.get(x, y1, pixel), options, exact);
+        }
+        for y in y0..=y1 {
+            uniform &= same_color(first, self.get(x0, y, pixel), options, exact);
+            uniform &= same_color(first, self.get(x1, y, pixel), options, exact);
+        }
+        if x1 - x0 < 2 || y1 - y0 < 2 {
+            // only border, nothing left inside
+            return;
+        }
+
+        if uniform {
+            for x in x0 + 1..x1 {
+                for y in y0 + 1..y1 {
+                    self.set(x, y, first);
+                }
+            }
+        } else if x1 - x0 <= MIN_SIZE || y1 - y0 <= MIN_SIZE {
+            for x in x0 + 1..x1 {
+                for y in y0 + 1..y1 {
+                    self.get(x, y, pixel);
+                }
+            }
+        } else {
+            let (xm, ym) = ((x0 + x1) / 2, (y0 + y1) / 2);
+            self.subdivide(x0, y0, xm, ym, pixel, options, exact, control);
+            self.subdivide(xm, y0, x1, ym, pixel, options, exact, control);
+            self.subdivide(x0, ym, xm, y1, pixel, options, exact, control);
+            self.subdivide(xm, ym, x1, y1, pixel, options, exact, control);
+        }
+    }
+}
+
+// smooth values and distances differ in every pixel, so only the interior can be filled then,
+// unless the interior coloring differs in every pixel as well. Trap distances differ everywhere.
+// So do orbit averages.
+// Interior borders also have to agree on the period, a rectangle around the pinch between
+// two components has an interior border but escaping points inside. Root finding converges fastest
+// at the roots, which can sit inside any border of equal iteration counts.
+fn same_color(a: Escape, b: Escape, options: &RenderOptions, exact: bool) -> bool {
+    if a.iterations != b.iterations || a.period != b.period || a.converged.is_some() || options.trap.is_some() {
+        return false;
+    }
+    if a.iterations == options.tries {
+        options.interior.is_uniform()
+    } else {
+        !(exact || options.smooth || options.tracks_derivative() || options.average().is_some())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use super::escapes;
+    use super::super::{mandelbrot, mandelbrot_field, RenderOptions, Strategy, Control};
+
+    #[test]
+    fn skips_uniform_rectangles() {
+        let options = RenderOptions::new(200, 1);
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = count.clone();
+        let columns = escapes(-2.1333, 2.1333, -1.2, 1.2, 320, 180, &options, Arc::new(Control::default()), false, move |x, y, options| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            mandelbrot(x, y, options)
+        });
+        assert_eq!(columns.len(), 320);
+        assert!(columns.iter().all(|column| column.len() == 180));
+        assert!(count.load(Ordering::Relaxed) < 320 * 180 / 2);
+    }
+
+    #[test]
+    fn fields_keep_every_escape() {
+        let mut options = RenderOptions::new(200, 1);
+        let brute = mandelbrot_field(-2.1333, 2.1333, -1.2, 1.2, 320, 180, &options);
+        options.strategy = Strategy::Subdivide;
+        let subdivided = mandelbrot_field(-2.1333, 2.1333, -1.2, 1.2, 320, 180, &options);
+        assert_eq!(subdivided.counts(), brute.counts());
+        // filled exterior would repeat the corner's z and smooth value
+        let exterior = brute.counts().into_iter().map(|count| count < options.tries);
+        for (((a, b), (z_a, z_b)), escaped) in subdivided.smooth().into_iter().zip(brute.smooth())
+            .zip(subdivided.final_z().into_iter().zip(brute.final_z())).zip(exterior) {
+            if escaped {
+                assert_eq!((a, z_a), (b, z_b));
+            }
+        }
+    }
+}
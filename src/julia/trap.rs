@@ -0,0 +1,76 @@
+use super::complex::Complex;
+
+// shape the orbits get measured against, in the coordinates of the complex plane
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Trap {
+    Point(Complex),
+    // through the point, angle in degrees counter clockwise from the real axis
+    Line(Complex, f64),
+    // horizontal and vertical line through the point
+    Cross(Complex),
+    // center and radius
+    Circle(Complex, f64),
+    // Pickover stalks: the cross, but only orbit points within width of it count,
+    // distances are relative to the width
+    Stalks(Complex, f64),
+}
+
+impl Trap {
+    // distance of z to the trap, None if it doesn't count
+    pub fn distance(&self, z: Complex) -> Option<f64> {
+        match *self {
+            Trap::Point(point) => Some((z - point).dist_from_origin()),
+            Trap::Line(point, angle) => {
+                let (sin, cos) = angle.to_radians().sin_cos();
+                let d = z - point;
+                Some((d.imag() * cos - d.real() * sin).abs())
+            }
+            Trap::Cross(point) => Some(cross(z - point)),
+            Trap::Circle(center, radius) => Some(((z - center).dist_from_origin() - radius).abs()),
+            Trap::Stalks(point, width) => {
+                let d = cross(z - point);
+                if d < width { Some(d / width) } else { None }
+            }
+        }
+    }
+
+    // closest the orbit came so far, after it reached z
+    pub fn closest(&self, closest: Option<f64>, z: Complex) -> Option<f64> {
+        match (closest, self.distance(z)) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+fn cross(d: Complex) -> f64 {
+    d.real().abs().min(d.imag().abs())
+}
+
+#[cfg(test)]
+mod test {
+    use super::Trap;
+    use super::super::complex::Complex;
+
+    #[test]
+    fn trap_distances() {
+        let z = Complex::new(3.0, 4.0);
+        let origin = Complex::null();
+        assert_eq!(Trap::Point(origin).distance(z), Some(5.0));
+        assert_eq!(Trap::Point(z).distance(z), Some(0.0));
+        assert_eq!(Trap::Line(origin, 0.0).distance(z), Some(4.0));
+        assert!((Trap::Line(origin, 90.0).distance(z).unwrap() - 3.0).abs() < 1e-12);
+        assert!(Trap::Line(origin, 53.130_102_354_155_98).distance(z).unwrap() < 1e-12);
+        assert_eq!(Trap::Cross(Complex::new(1.0, 1.0)).distance(z), Some(2.0));
+        assert_eq!(Trap::Circle(origin, 2.0).distance(z), Some(3.0));
+        assert_eq!(Trap::Circle(origin, 7.0).distance(z), Some(2.0));
+
+        // stalks only catch what comes close
+        let stalks = Trap::Stalks(origin, 4.0);
+        assert_eq!(stalks.distance(z), Some(0.75));
+        assert_eq!(stalks.distance(Complex::new(5.0, 5.0)), None);
+        assert_eq!(stalks.closest(None, Complex::new(5.0, 5.0)), None);
+        assert_eq!(stalks.closest(Some(0.5), z), Some(0.5));
+        assert_eq!(stalks.closest(Some(0.5), Complex::new(5.0, 5.0)), Some(0.5));
+    }
+}
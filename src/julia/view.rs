@@ -0,0 +1,257 @@
+use dashu_float::{DBig, FBig};
+use std::f64::consts::LOG2_10;
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ParseError {
+    // not a decimal number
+    Number(dashu_base::ParseError),
+    // spans have to be positive, anything else renders a degenerate or mirrored view
+    Span,
+}
+
+impl From<dashu_base::ParseError> for ParseError {
+    fn from(e: dashu_base::ParseError) -> Self {
+        ParseError::Number(e)
+    }
+}
+
+use super::{X_DIF, Y_DIF};
+use super::floatexp::FloatExp;
+
+// extra digits on top of what the span needs, so pixel offsets don't get rounded away
+const PRECISION_MARGIN: usize = 20;
+
+// significant digits kept for the spans themselves
+const SPAN_DIGITS: usize = 40;
+
+/// Visible rectangle of the complex plane, stored as center and span in arbitrary precision decimals.
+/// Decimals round trip through strings exactly, so views can be saved and restored without drift.
+#[derive(Clone, Debug)]
+pub struct View {
+    center_x: DBig,
+    center_y: DBig,
+    x_dif: DBig,
+    y_dif: DBig,
+}
+
+impl View {
+    // fully zoomed out view
+    pub fn new() -> Self {
+        View {
+            center_x: DBig::ZERO,
+            center_y: DBig::ZERO,
+            x_dif: decimal_from_f64(2.0 * X_DIF),
+            y_dif: decimal_from_f64(2.0 * Y_DIF),
+        }
+    }
+
+    pub fn from_strings(center_x: &str, center_y: &str, x_dif: &str, y_dif: &str) -> Result<Self, ParseError> {
+        let mut view = View {
+            center_x: DBig::from_str(center_x)?,
+            center_y: DBig::from_str(center_y)?,
+            x_dif: DBig::from_str(x_dif)?,
+            y_dif: DBig::from_str(y_dif)?,
+        };
+        if view.x_dif <= DBig::ZERO || view.y_dif <= DBig::ZERO {
+            return Err(ParseError::Span);
+        }
+        let digits = view.digits();
+        view.center_x = with_digits(&view.center_x, digits);
+        view.center_y = with_digits(&view.center_y, digits);
+        Ok(view)
+    }
+
+    // (center_x, center_y, x_dif, y_dif)
+    pub fn to_strings(&self) -> (String, String, String, String) {
+        (
+            self.center_x.to_string(),
+            self.center_y.to_string(),
+            self.x_dif.to_string(),
+            self.y_dif.to_string(),
+        )
+    }
+
+    // decimal digits needed to tell pixels apart in the current span
+    pub fn digits(&self) -> usize {
+        let smaller = if self.x_dif < self.y_dif { &self.x_dif } else { &self.y_dif };
+        let magnitude = smaller.repr().exponent() + smaller.repr().digits() as isize;
+        (-magnitude).max(0) as usize + PRECISION_MARGIN
+    }
+
+    // binary precision matching digits()
+    pub fn precision(&self) -> usize {
+        (self.digits() as f64 * LOG2_10).ceil() as usize
+    }
+
+    pub fn pix_to_cords(&self, p: (f64, f64), pix_dim: (u32, u32)) -> (DBig, DBig) {
+        let digits = self.digits();
+        let x_ratio = decimal_from_f64(p.0 / pix_dim.0 as f64 - 0.5);
+        let y_ratio = decimal_from_f64(p.1 / pix_dim.1 as f64 - 0.5);
+        let x = with_digits(&self.center_x, digits) + with_digits(&x_ratio, digits) * with_digits(&self.x_dif, digits);
+        let y = with_digits(&self.center_y, digits) + with_digits(&y_ratio, digits) * with_digits(&self.y_dif, digits);
+        (x, y)
+    }
+
+    pub fn move_to(&mut self, x: DBig, y: DBig) {
+        self.center_x = x;
+        self.center_y = y;
+    }
+
+    pub fn zoom(&mut self, p: (DBig, DBig), factor: f64) {
+        let old = self.clone();
+        let factor = with_digits(&decimal_from_f64(factor), SPAN_DIGITS);
+        self.x_dif = with_digits(&old.x_dif, SPAN_DIGITS) * &factor;
+        self.y_dif = with_digits(&old.y_dif, SPAN_DIGITS) * &factor;
+        let digits = self.digits();
+        let (mut x, mut y) = (with_digits(&p.0, digits), with_digits(&p.1, digits));
+
+        if factor < DBig::ONE {
+            // new window should be contained in old window
+            x = fit(x, &old.center_x, &old.x_dif, &self.x_dif, digits);
+            y = fit(y, &old.center_y, &old.y_dif, &self.y_dif, digits);
+        }
+
+        self.center_x = x;
+        self.center_y = y;
+    }
+
+    // center in binary floats, precise enough for a reference orbit
+    pub fn center_binary(&self) -> (FBig, FBig) {
+        let precision = self.precision();
+        (to_binary(&self.center_x, precision), to_binary(&self.center_y, precision))
+    }
+
+    // span in FloatExp, which doesn't underflow like span() does past 1e-308
+    pub fn span_exp(&self) -> (FloatExp, FloatExp) {
+        (to_floatexp(&self.x_dif), to_floatexp(&self.y_dif))
+    }
+}
+
+impl Default for View {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// moves center so that the new span fits inside the old one
+fn fit(center: DBig, old_center: &DBig, old_dif: &DBig, new_dif: &DBig, digits: usize) -> DBig {
+    let half = with_digits(&DBig::from_str("0.5").unwrap(), digits);
+    let old_center = with_digits(old_center, digits);
+    let old_min = &old_center - &half * old_dif;
+    let old_max = &old_center + &half * old_dif;
+    let new_half = &half * new_dif;
+    if &center - &new_half < old_min {
+        old_min + new_half
+    } else if &center + &new_half > old_max {
+        old_max - new_half
+    } else {
+        center
+    }
+}
+
+// shortest decimal that round trips to x, so 2.1333 doesn't turn into 2.13329999999999997...
+pub fn decimal_from_f64(x: f64) -> DBig {
+    DBig::from_str(&x.to_string()).expect("cords have to be finite")
+}
+
+// operations keep the precision of their operands, so they have to be set before calculating
+fn with_digits(x: &DBig, digits: usize) -> DBig {
+    x.clone().with_precision(digits).value()
+}
+
+fn to_floatexp(x: &DBig) -> FloatExp {
+    let (significand, exponent) = to_binary(x, 64).into_repr().into_parts();
+    FloatExp::from_parts(significand.to_f64().value(), exponent as i64)
+}
+
+pub fn to_binary(x: &DBig, precision: usize) -> FBig {
+    x.clone().with_base_and_precision::<2>(precision).value().with_rounding()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{View, ParseError};
+    use super::super::floatexp::FloatExp;
+
+    #[test]
+    fn string_round_trip() {
+        let cx = "-0.74364388703715870475219150611477";
+        let cy = "0.13182590420533516757356179289616";
+        let view = View::from_strings(cx, cy, "1e-30", "5.625e-31").unwrap();
+        let (x, y, _, _) = view.to_strings();
+        let restored = View::from_strings(&x, &y, "1e-30", "5.625e-31").unwrap();
+        assert_eq!(view.to_strings(), restored.to_strings());
+        assert_eq!(x, cx);
+        assert_eq!(y, cy);
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!(View::from_strings("abc", "0", "1", "1").is_err());
+        assert_eq!(View::from_strings("0", "0", "0", "1").unwrap_err(), ParseError::Span);
+        assert_eq!(View::from_strings("0", "0", "1", "-1e-40").unwrap_err(), ParseError::Span);
+    }
+
+    #[test]
+    fn deep_zoom_keeps_center() {
+        // zoom into the same pixel again and again, far past f64
+        let mut view = View::new();
+        let dim = (160, 90);
+        for _ in 0..200 {
+            let p = view.pix_to_cords((100.0, 40.0), dim);
+            view.zoom(p, 0.5);
+        }
+        let (x_dif, _) = view.span_exp();
+        assert!(x_dif < FloatExp::new(1e-59));
+        // the pixel left of the center has to map to a different cord than the center
+        let a = view.pix_to_cords((80.0, 45.0), dim);
+        let b = view.pix_to_cords((79.0, 45.0), dim);
+        assert!(a.0 != b.0);
+        assert!(a.1 == b.1);
+    }
+
+    #[test]
+    fn move_and_zoom_out() {
+        let mut view = View::new();
+        let dim = (16, 9);
+        let p = view.pix_to_cords((16.0, 9.0), dim);
+        view.move_to(p.0, p.1);
+        let (cx, cy, _, _) = view.to_strings();
+        assert_eq!((cx.as_str(), cy.as_str()), ("2.1333", "1.2"));
+
+        let p = view.pix_to_cords((8.0, 4.5), dim);
+        view.zoom(p, 2.0);
+        let (_, _, x_dif, y_dif) = view.to_strings();
+        assert_eq!((x_dif.as_str(), y_dif.as_str()), ("8.5332", "4.8"));
+    }
+
+    #[test]
+    fn zoom_in_stays_inside() {
+        let mut view = View::new();
+        let dim = (16, 9);
+        // zooming at the corner clamps the new window to the old one
+        let p = view.pix_to_cords((0.0, 0.0), dim);
+        view.zoom(p, 0.5);
+        let (cx, cy, _, _) = view.to_strings();
+        assert_eq!((cx.as_str(), cy.as_str()), ("-1.06665", "-0.6"));
+    }
+
+    #[test]
+    fn span_beyond_f64() {
+        let view = View::from_strings("0", "0", "3e-1000", "1.5e-1000").unwrap();
+        let (x_dif, y_dif) = view.span_exp();
+        assert_eq!(x_dif.to_f64(), 0.0);
+        assert_eq!((x_dif / y_dif).to_f64(), 2.0);
+        let scaled = x_dif * FloatExp::new(1e300) * FloatExp::new(1e300) * FloatExp::new(1e300) * FloatExp::new(1e100);
+        assert!((scaled.to_f64() - 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn precision_grows_with_zoom() {
+        let shallow = View::from_strings("0", "0", "1e-10", "1e-10").unwrap();
+        let deep = View::from_strings("0", "0", "1e-300", "1e-300").unwrap();
+        assert!(deep.precision() > shallow.precision());
+        assert!(deep.precision() >= 997);
+    }
+}
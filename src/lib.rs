@@ -1,20 +1,36 @@
+#![allow(clippy::too_many_arguments)]
+
 mod julia;
+mod frame;
 
 use pyo3::prelude::*;
+use pyo3::exceptions::{PyException, PyIOError, PyRuntimeError, PyValueError};
+use pyo3::types::{PyBytes, PyComplex};
+use image::RgbImage;
+use dashu_float::{DBig, FBig};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use julia::floatexp::FloatExp;
+use julia::colors::{Palette, Interpolation, Coloring, Lighting};
+use julia::colors::gradients::{self, GradientError};
+use julia::interior::Interior;
+use julia::trap::Trap;
+use julia::polynomial::{Polynomial, PolynomialError};
+use julia::newton::RootFinder;
+use frame::Frame;
 
-const MANDEL_FILE: &str = &"./renders/mandel.png";
-const JULIA_FILE: &str = &"./renders/julia.png";
+pyo3::create_exception!(mandelbrot_module, RenderCancelled, PyException);
 
 #[pyclass]
 pub struct PlotWindow {
     pixel_dim: (u32, u32),
-    x_min: f64,
-    x_max: f64,
-    x_dif: f64,
-    y_min: f64,
-    y_max: f64,
-    y_dif: f64,
-    julia: julia::Julia
+    view: julia::view::View,
+    julia: julia::Julia,
+    options: julia::RenderOptions,
+    skipped: u32
 }
 
 #[pymethods]
@@ -22,175 +38,732 @@ impl PlotWindow {
     #[new]
     fn __new__(pixel_dim: (u32, u32)) -> Self {
         // creates Plotwindow at fully zoomed out view
-        let x_max = julia::X_DIF;
-        let x_min = - julia::X_DIF;
-        let x_dif = x_max - x_min;
-        let y_max = julia::Y_DIF;
-        let y_min = - julia::Y_DIF;
-        let y_dif = y_max - y_min;
+        let view = julia::view::View::new();
         let julia = julia::Julia::new(0.0, 0.0);
+        let options = julia::RenderOptions::new(0, 1);
         PlotWindow {
-            pixel_dim, x_min, x_max, x_dif, y_min, y_max, y_dif, julia
+            pixel_dim, view, julia, options, skipped: 0
         }
     }
 
     fn __repr__(&self) -> PyResult<String> {
         let dim = self.pixel_dim;
-        Ok(format!("({}, {}): x=({}, {}) y=({}, {})", dim.0, dim.1, self.x_min, self.x_max, self.y_min, self.y_max))
+        let (cx, cy, x_dif, y_dif) = self.view.to_strings();
+        Ok(format!("({}, {}): center=({}, {}) span=({}, {})", dim.0, dim.1, cx, cy, x_dif, y_dif))
+    }
+
+    // (center_x, center_y, x_span, y_span) as decimal strings, exact enough to restore the view with set_view
+    fn get_view(&self) -> PyResult<(String, String, String, String)> {
+        Ok(self.view.to_strings())
+    }
+
+    fn set_view(&mut self, center_x: &str, center_y: &str, x_span: &str, y_span: &str) -> PyResult<()> {
+        self.view = julia::view::View::from_strings(center_x, center_y, x_span, y_span).map_err(parse_error)?;
+        Ok(())
     }
 
     fn move_view(&mut self, p: (f64, f64)) -> PyResult<()> {
-        let (new_x, new_y) = pix_to_cords(p, self.pixel_dim.clone(), self.x_min.clone(), self.x_dif.clone(), self.y_min.clone(), self.y_dif.clone());
-        let x_offset = self.x_dif / 2.0;
-        self.x_min = new_x - x_offset;
-        self.x_max = new_x + x_offset;
-        let y_offset = self.y_dif / 2.0;
-        self.y_min = new_y - y_offset;
-        self.y_max = new_y + y_offset;
+        let (new_x, new_y) = self.view.pix_to_cords(p, self.pixel_dim);
+        self.view.move_to(new_x, new_y);
         Ok(())
     }
 
     fn zoom(&mut self, p: (f64, f64), factor: f64) -> PyResult<()> {
-        let p = pix_to_cords(p, self.pixel_dim.clone(), self.x_min.clone(), self.x_dif.clone(), self.y_min.clone(), self.y_dif.clone());
-        self.zoom_main(p, factor)
+        let p = self.view.pix_to_cords(p, self.pixel_dim);
+        self.view.zoom(p, factor);
+        Ok(())
+    }
+
+    // zooms at the cords given as decimal strings
+    fn zoom_main(&mut self, p: (&str, &str), factor: f64) -> PyResult<()> {
+        let x = DBig::from_str(p.0).map_err(parse_error)?;
+        let y = DBig::from_str(p.1).map_err(parse_error)?;
+        self.view.zoom((x, y), factor);
+        Ok(())
+    }
+
+    // iterations the series approximation skipped in the last render
+    fn skipped_iterations(&self) -> PyResult<u32> {
+        Ok(self.skipped)
+    }
+
+    // color by the fractional escape value instead of the iteration count
+    fn set_smooth(&mut self, smooth: bool) -> PyResult<()> {
+        self.options.smooth = smooth;
+        Ok(())
+    }
+
+    // track derivatives for EscapeField.distance, the distance and boundary colorings do anyway
+    fn set_derivative(&mut self, derivative: bool) -> PyResult<()> {
+        self.options.derivative = derivative;
+        Ok(())
+    }
+
+    // skip the rest of the tries for interior points once their orbit repeats
+    fn set_periodicity(&mut self, periodicity: bool) -> PyResult<()> {
+        self.options.periodicity = periodicity;
+        Ok(())
+    }
+
+    // strategy is brute or subdivide
+    fn set_strategy(&mut self, strategy: &str) -> PyResult<()> {
+        self.options.strategy = julia::Strategy::from_name(strategy)
+            .ok_or_else(|| PyValueError::new_err(format!("unknown render strategy: {}", strategy)))?;
+        Ok(())
+    }
+
+    // name is one of palettes() or the path of a .map, .ggr or .ugr gradient file.
+    // interpolation is one of rgb, linear, hsv, oklab or None for the palette's own,
+    // cycle is the number of iterations per run through the palette (None keeps the palette's own),
+    // offset shifts it by a fraction of a run
+    #[args(cycle = "None", offset = "0.0", reverse = "false", interpolation = "None")]
+    fn set_palette(&mut self, name: &str, cycle: Option<f64>, offset: f64, reverse: bool, interpolation: Option<String>) -> PyResult<()> {
+        self.options.palette = palette(name, cycle, offset, reverse, interpolation.as_deref())?;
+        Ok(())
+    }
+
+    // coloring is cycle (through the palette every cycle iterations), histogram (spread evenly over the frame),
+    // distance (by distance to the set), boundary (black within a pixel of the set, white elsewhere),
+    // trap (by the closest approach to the orbit trap, see set_trap), stripe (stripe average, see set_stripes),
+    // triangle (triangle inequality average) or roots (by the root reached and how fast, see set_newton).
+    // The distance estimates and the averages need a large escape radius, see set_bailout.
+    // Like the trap, only the shallow kernels record the averages, past the zoom f64 can resolve
+    // stripe and triangle fall back to the escape value
+    fn set_coloring(&mut self, coloring: &str) -> PyResult<()> {
+        self.options.coloring = coloring_from_name(coloring)?;
+        Ok(())
+    }
+
+    // interior is black, magnitude (final |z|), minimum (smallest |z| of the orbit), period,
+    // distance (to the boundary, mandelbrot set only) or multiplier (angle of the attracting cycle's multiplier).
+    // Past the zoom f64 can resolve the cycle isn't searched for, period, distance and multiplier stay black there
+    fn set_interior(&mut self, interior: &str) -> PyResult<()> {
+        self.options.interior = interior_from_name(interior)?;
+        Ok(())
+    }
+
+    // turns of the stripes per turn of arg z for the stripe coloring
+    fn set_stripes(&mut self, density: f64) -> PyResult<()> {
+        if !density.is_finite() {
+            return Err(PyValueError::new_err(format!("stripe density has to be finite: {}", density)));
+        }
+        self.options.stripes = density;
+        Ok(())
+    }
+
+    // orbit trap in the coordinates of the complex plane: none, point, line (through the point at angle degrees),
+    // cross (horizontal and vertical line through the point), circle (around the point) or stalks
+    // (Pickover stalks, the cross counting only within width of it). Only the shallow kernels record it
+    #[args(x = "0.0", y = "0.0", angle = "0.0", radius = "1.0", width = "0.1")]
+    fn set_trap(&mut self, trap: &str, x: f64, y: f64, angle: f64, radius: f64, width: f64) -> PyResult<()> {
+        let point = julia::complex::Complex::new(x, y);
+        self.options.trap = match trap {
+            "none" => None,
+            "point" => Some(Trap::Point(point)),
+            "line" => Some(Trap::Line(point, angle)),
+            "cross" => Some(Trap::Cross(point)),
+            "circle" => Some(Trap::Circle(point, radius)),
+            "stalks" if width > 0.0 => Some(Trap::Stalks(point, width)),
+            "stalks" => return Err(PyValueError::new_err(format!("stalk width has to be positive: {}", width))),
+            _ => return Err(PyValueError::new_err(format!("unknown orbit trap: {}", trap))),
+        };
+        Ok(())
     }
 
-    fn zoom_main(&mut self, p: (f64, f64), factor: f64) -> PyResult<()> {
-        let new_x_dif = self.x_dif * factor;
-        let new_y_dif = self.y_dif * factor;
+    // shades the colors like a relief lit from angle degrees (counter clockwise from the positive real axis)
+    // and elevation degrees above the plane. ambient and specular are in [0, 1], height makes the relief steeper
+    #[args(enabled = "true", angle = "45.0", elevation = "45.0", ambient = "0.3", specular = "0.3", height = "1.0")]
+    fn set_lighting(&mut self, enabled: bool, angle: f64, elevation: f64, ambient: f64, specular: f64, height: f64) -> PyResult<()> {
+        self.options.lighting = lighting(enabled, angle, elevation, ambient, specular, height)?;
+        Ok(())
+    }
 
-        let mut new_x_min = p.0 - 0.5 * new_x_dif;
-        let mut new_x_max = p.0 + 0.5 * new_x_dif;
+    // formula is mandelbrot, burning_ship, tricorn (or mandelbar), celtic, perpendicular or buffalo,
+    // load_julia renders its dynamic plane. Only mandelbrot zooms deeper than f64 goes
+    fn set_formula(&mut self, formula: &str) -> PyResult<()> {
+        self.options.formula = julia::formula::Formula::from_name(formula)
+            .ok_or_else(|| PyValueError::new_err(format!("unknown formula: {}", formula)))?;
+        Ok(())
+    }
 
-        let mut new_y_min = p.1 - 0.5 * new_y_dif;
-        let mut new_y_max = p.1 + 0.5 * new_y_dif;
+    // real or complex exponent the formula raises z to instead of the power passed to the loads,
+    // 2.5 renders z^2.5 + c. Powers are taken on the principal branch, arg z in (-pi, pi].
+    // None goes back to the power
+    #[args(exponent = "None")]
+    fn set_exponent(&mut self, py: Python, exponent: Option<PyObject>) -> PyResult<()> {
+        self.options.exponent = exponent.map(|exponent| complex(exponent.as_ref(py))).transpose()?;
+        Ok(())
+    }
 
-        if factor < 1.0 {
-            // new window should be contained in old window
-            // fit x
-            if new_x_min < self.x_min.clone() {
-                new_x_min = self.x_min.clone();
-                new_x_max = new_x_min + new_x_dif;
-            }else if new_x_max > self.x_max.clone() {
-                new_x_max = self.x_max.clone();
-                new_x_min = new_x_max - new_x_dif;
+    // renders phoenix, magnet1, magnet2 or lambda (also logistic) instead of the formula or polynomial.
+    // load_mandelbrot takes the pixel as c, load_julia the julia point. phoenix (a number) weighs the
+    // previous z in z^d + c + p z_prev, the power passed to the loads is d. None goes back to the formula
+    #[args(phoenix = "None")]
+    fn set_family(&mut self, py: Python, family: Option<String>, phoenix: Option<PyObject>) -> PyResult<()> {
+        self.options.family = match family {
+            Some(name) => Some(julia::family::Family::from_name(&name)
+                .ok_or_else(|| PyValueError::new_err(format!("unknown fractal family: {}", name)))?),
+            None => None,
+        };
+        if let Some(phoenix) = phoenix {
+            self.options.phoenix = complex(phoenix.as_ref(py))?;
+        }
+        Ok(())
+    }
+
+    // renders the polynomial a_0 + a_1 z + ... + a_n z^n instead of the formula, coefficients are numbers
+    // lowest power first. load_mandelbrot takes the coefficient at index parameter as the pixel, load_julia
+    // as the julia point. The power passed to the loads doesn't matter then, None goes back to the formula
+    #[args(parameter = "0")]
+    fn set_polynomial(&mut self, coefficients: Option<Vec<&PyAny>>, parameter: usize) -> PyResult<()> {
+        let coefficients = match coefficients {
+            Some(coefficients) => coefficients,
+            None => {
+                self.options.polynomial = None;
+                return Ok(());
             }
-            // fit y
-            if new_y_min < self.y_min.clone() {
-                new_y_min = self.y_min.clone();
-                new_y_max = new_y_min + new_y_dif;
-            }else if new_y_max > self.y_max.clone() {
-                new_y_max = self.y_max.clone();
-                new_y_min = new_y_max - new_y_dif;
+        };
+        let coefficients = coefficients.into_iter().map(complex).collect::<PyResult<Vec<_>>>()?;
+        let polynomial = Polynomial::new(coefficients, parameter).map_err(polynomial_error)?;
+        self.options.polynomial = Some(Arc::new(polynomial));
+        Ok(())
+    }
+
+    // renders the basins of a root finding method for the polynomial with the given roots, or coefficients
+    // lowest power first, instead of the formula or polynomial. method is newton or halley, relaxation
+    // (a number) scales the steps. With nova the pixel gets added after every step in load_mandelbrot,
+    // the julia point in load_julia. None goes back to the escape time fractals
+    #[args(coefficients = "false", method = "\"newton\"", relaxation = "None", nova = "false")]
+    fn set_newton(&mut self, py: Python, values: Option<Vec<&PyAny>>, coefficients: bool, method: &str, relaxation: Option<PyObject>, nova: bool) -> PyResult<()> {
+        let values = match values {
+            Some(values) => values.into_iter().map(complex).collect::<PyResult<Vec<_>>>()?,
+            None => {
+                self.options.newton = None;
+                return Ok(());
             }
-        }
-        
-        self.x_min = new_x_min; 
-        self.x_max = new_x_max; 
-        self.x_dif = new_x_dif; 
-        self.y_min = new_y_min; 
-        self.y_max = new_y_max;
-        self.y_dif = new_y_dif;
+        };
+        let method = julia::newton::Method::from_name(method)
+            .ok_or_else(|| PyValueError::new_err(format!("unknown root finding method: {}", method)))?;
+        let relaxation = relaxation.map_or(Ok(julia::complex::Complex::new(1.0, 0.0)), |relaxation| complex(relaxation.as_ref(py)))?;
+        let finder = if coefficients {
+            RootFinder::from_coefficients(values, method, relaxation, nova)
+        } else {
+            RootFinder::from_roots(values, method, relaxation, nova)
+        };
+        self.options.newton = Some(Arc::new(finder.map_err(polynomial_error)?));
+        Ok(())
+    }
 
+    // norm is one of euclidean, manhattan, max, real, imag
+    fn set_bailout(&mut self, radius: f64, norm: &str) -> PyResult<()> {
+        if !(radius > 0.0 && radius.is_finite()) {
+            return Err(PyValueError::new_err(format!("escape radius has to be positive: {}", radius)));
+        }
+        self.options.bailout = julia::Bailout::from_name(norm)
+            .ok_or_else(|| PyValueError::new_err(format!("unknown bailout norm: {}", norm)))?;
+        self.options.radius = radius;
         Ok(())
     }
 
-    fn load_mandelbrot(&self, tries: u32, power: u32) -> PyResult<String> {
-        let dim = self.pixel_dim;
-        julia::fine_mandelbrot(
-            self.x_min.clone(), 
-            self.x_max.clone(), 
-            self.y_min.clone(), 
-            self.y_max.clone(), 
-            dim.0, dim.1, 
-            MANDEL_FILE, 
-            tries, power
-        );
-        Ok(String::from(MANDEL_FILE))
-    }
-
-    fn load_julia(&self, tries: u32, power: u32) -> PyResult<String> {
-        let dim = self.pixel_dim;
-        let jul = self.julia.clone();
-        julia::main_julia(
-            jul, 
-            self.x_min.clone(), 
-            self.x_max.clone(), 
-            self.y_min.clone(), 
-            self.y_max.clone(), 
-            dim.0 ,dim.1, 
-            JULIA_FILE, 
-            tries, power
-        );
-        Ok(String::from(JULIA_FILE))
+    // rgb frame of the current view shaped (height, width, 3), as numpy array if numpy is true.
+    // preview(buffer, width, height) gets the raw rgb buffer after every pass of a progressive render
+    #[args(preview = "None", numpy = "false")]
+    fn load_mandelbrot(&mut self, py: Python, tries: u32, power: u32, preview: Option<PyObject>, numpy: bool) -> PyResult<PyObject> {
+        let job = self.job(tries, power, None);
+        self.load(py, job, preview, numpy)
+    }
+
+    #[args(preview = "None", numpy = "false")]
+    fn load_julia(&mut self, py: Python, tries: u32, power: u32, preview: Option<PyObject>, numpy: bool) -> PyResult<PyObject> {
+        let job = self.job(tries, power, Some(self.julia));
+        self.load(py, job, preview, numpy)
+    }
+
+    // same as load_mandelbrot, but returns right away with a handle to the render running in the background
+    #[args(preview = "None")]
+    fn start_mandelbrot(&mut self, tries: u32, power: u32, preview: Option<PyObject>) -> PyResult<RenderHandle> {
+        let job = self.job(tries, power, None);
+        Ok(RenderHandle::start(job, preview))
+    }
+
+    #[args(preview = "None")]
+    fn start_julia(&mut self, tries: u32, power: u32, preview: Option<PyObject>) -> PyResult<RenderHandle> {
+        let job = self.job(tries, power, Some(self.julia));
+        Ok(RenderHandle::start(job, preview))
+    }
+
+    // escapes of the current view without coloring them, for coloring them again later
+    fn mandelbrot_field(&mut self, py: Python, tries: u32, power: u32) -> PyResult<EscapeField> {
+        let job = self.job(tries, power, None);
+        let (field, skipped) = py.allow_threads(|| job.field());
+        self.skipped = skipped;
+        Ok(EscapeField::new(field))
+    }
+
+    fn julia_field(&mut self, py: Python, tries: u32, power: u32) -> PyResult<EscapeField> {
+        let job = self.job(tries, power, Some(self.julia));
+        let (field, skipped) = py.allow_threads(|| job.field());
+        self.skipped = skipped;
+        Ok(EscapeField::new(field))
     }
 
     fn set_julia(&mut self, j_pix_cords: (f64, f64)) -> PyResult<()> {
-        let j_cords = pix_to_cords(j_pix_cords, self.pixel_dim.clone(), self.x_min.clone(), self.x_dif.clone(), self.y_min.clone(), self.y_dif.clone()); 
-        let julia = julia::Julia::new(j_cords.0, j_cords.1);
+        let (jx, jy) = self.view.pix_to_cords(j_pix_cords, self.pixel_dim);
+        let julia = julia::Julia::new(jx.to_f64().value(), jy.to_f64().value());
         self.julia = julia;
         Ok(())
     }
 
     fn reset_view(&mut self) -> PyResult<()> {
-        self.x_min = - julia::X_DIF;
-        self.x_max = julia::X_DIF;
-        self.x_dif = self.x_max - self.x_min;
-        self.y_min = - julia::Y_DIF;
-        self.y_max = julia::Y_DIF;
-        self.y_dif = self.y_max - self.y_min;
+        self.view = julia::view::View::new();
+        Ok(())
+    }
+}
+
+impl PlotWindow {
+    fn load(&mut self, py: Python, job: RenderJob, preview: Option<PyObject>, numpy: bool) -> PyResult<PyObject> {
+        let (img, skipped) = py.allow_threads(|| job.run(preview.as_ref(), Arc::default()))?;
+        self.skipped = skipped;
+        Frame::rgb(img.width(), img.height(), img.into_raw()).into_py_result(py, numpy)
+    }
+
+    fn job(&mut self, tries: u32, power: u32, julia: Option<julia::Julia>) -> RenderJob {
+        self.options.tries = tries;
+        // the smooth colorings need the degree of what gets iterated
+        self.options.power = match &self.options.polynomial {
+            Some(polynomial) => polynomial.degree() as u32 - 1,
+            None => power,
+        };
+        RenderJob {
+            julia,
+            center: self.view.center_binary(),
+            span: self.view.span_exp(),
+            pixel_dim: self.pixel_dim,
+            options: self.options.clone(),
+        }
+    }
+}
+
+// everything a render of the current view needs, owned so it can move to another thread
+struct RenderJob {
+    // renders the mandelbrot set if there is no julia
+    julia: Option<julia::Julia>,
+    center: (FBig, FBig),
+    span: (FloatExp, FloatExp),
+    pixel_dim: (u32, u32),
+    options: julia::RenderOptions,
+}
+
+impl RenderJob {
+    // returns the image and the skipped iterations, preview(buffer, width, height) takes the GIL for every pass
+    // and the first error it raises is returned once the render is done
+    fn run(&self, preview: Option<&PyObject>, control: Arc<julia::Control>) -> PyResult<(RgbImage, u32)> {
+        let mut error = None;
+        let mut call = |img: &RgbImage| {
+            if let (Some(preview), None) = (preview, &error) {
+                Python::with_gil(|py| {
+                    let buffer = PyBytes::new(py, img.as_raw());
+                    if let Err(e) = preview.call1(py, (buffer, img.width(), img.height())) {
+                        error = Some(e);
+                    }
+                });
+            }
+        };
+        let watch = julia::Watch {
+            preview: if preview.is_some() { Some(&mut call) } else { None },
+            control,
+        };
+        let ((cx, cy), (x_dif, y_dif), dim) = (&self.center, self.span, self.pixel_dim);
+        let rendered = match self.julia {
+            None => julia::deep_mandelbrot(cx, cy, x_dif, y_dif, dim.0, dim.1, &self.options, watch),
+            Some(jul) => julia::deep_julia(jul, cx, cy, x_dif, y_dif, dim.0, dim.1, &self.options, watch),
+        };
+        match error {
+            Some(e) => Err(e),
+            None => Ok(rendered),
+        }
+    }
+
+    fn field(&self) -> (julia::field::Field, u32) {
+        let ((cx, cy), (x_dif, y_dif), dim) = (&self.center, self.span, self.pixel_dim);
+        julia::deep_field(self.julia, cx, cy, x_dif, y_dif, dim.0, dim.1, &self.options, Arc::default())
+    }
+}
+
+/// Render running on its own thread, returned by PlotWindow.start_mandelbrot and start_julia.
+/// Every handle keeps its own image, dropping it cancels the render
+#[pyclass]
+pub struct RenderHandle {
+    control: Arc<julia::Control>,
+    thread: Option<JoinHandle<PyResult<(RgbImage, u32)>>>,
+    // the image is None once result() took it
+    outcome: Option<PyResult<(Option<RgbImage>, u32)>>,
+}
+
+impl RenderHandle {
+    fn start(job: RenderJob, preview: Option<PyObject>) -> Self {
+        let control = Arc::new(julia::Control::default());
+        let thread_control = control.clone();
+        let thread = thread::spawn(move || {
+            let rendered = job.run(preview.as_ref(), thread_control.clone())?;
+            if thread_control.is_cancelled() {
+                return Err(RenderCancelled::new_err("render was cancelled"));
+            }
+            Ok(rendered)
+        });
+        RenderHandle { control, thread: Some(thread), outcome: None }
+    }
+
+    // waits for the render thread, without holding the GIL
+    fn wait(&mut self, py: Python) -> PyResult<&mut (Option<RgbImage>, u32)> {
+        if let Some(thread) = self.thread.take() {
+            let outcome = py.allow_threads(|| thread.join())
+                .unwrap_or_else(|_| Err(PyRuntimeError::new_err("render thread panicked")));
+            self.outcome = Some(outcome.map(|(img, skipped)| (Some(img), skipped)));
+        }
+        match self.outcome.as_mut().expect("thread is joined") {
+            Ok(rendered) => Ok(rendered),
+            Err(e) => Err(e.clone_ref(py)),
+        }
+    }
+}
+
+impl Drop for RenderHandle {
+    // nobody can get the image anymore, the thread finishes on its own without computing the rest
+    fn drop(&mut self) {
+        self.control.cancel();
+    }
+}
+
+#[pymethods]
+impl RenderHandle {
+    // the reference orbit and the workers stop before their next step, result() raises RenderCancelled
+    fn cancel(&self) -> PyResult<()> {
+        self.control.cancel();
         Ok(())
     }
+
+    fn is_done(&self) -> PyResult<bool> {
+        Ok(self.thread.as_ref().is_none_or(|thread| thread.is_finished()))
+    }
+
+    // fraction of the pixels that are done, from 0.0 to 1.0. Deep renders count the reference orbit in
+    fn progress(&self) -> PyResult<f64> {
+        Ok(self.control.progress())
+    }
+
+    // blocks until the render is done and returns the rgb frame like load_mandelbrot.
+    // The frame takes the image over, so it can only be taken once
+    #[args(numpy = "false")]
+    fn result(&mut self, py: Python, numpy: bool) -> PyResult<PyObject> {
+        let img = self.wait(py)?.0.take()
+            .ok_or_else(|| PyRuntimeError::new_err("result was already taken"))?;
+        Frame::rgb(img.width(), img.height(), img.into_raw()).into_py_result(py, numpy)
+    }
+
+    // iterations the series approximation skipped, blocks like result()
+    fn skipped_iterations(&mut self, py: Python) -> PyResult<u32> {
+        Ok(self.wait(py)?.1)
+    }
+}
+
+/// Escapes of a whole frame, returned by PlotWindow.mandelbrot_field and julia_field.
+/// Coloring it again takes milliseconds, rendering it took the full iteration.
+#[pyclass]
+pub struct EscapeField {
+    field: julia::field::Field,
+    // what it gets colored with, starts out as the options it was rendered with
+    options: julia::RenderOptions,
 }
 
-fn pix_to_cords(p: (f64, f64), pix_dim: (u32, u32), x_min: f64, x_dif: f64, y_min: f64, y_dif: f64) -> (f64, f64) {
-    let x = x_min + (p.0 / pix_dim.0 as f64) * x_dif;
-    let y = y_min + (p.1 / pix_dim.1 as f64) * y_dif;
-    (x, y)
+impl EscapeField {
+    fn new(field: julia::field::Field) -> Self {
+        let options = field.options().clone();
+        EscapeField { field, options }
+    }
+
+    fn image(&self, py: Python, smooth: bool) -> RgbImage {
+        let mut options = self.options.clone();
+        options.smooth = smooth;
+        py.allow_threads(|| julia::colors::color_field(&self.field, &options))
+    }
+}
+
+#[pymethods]
+impl EscapeField {
+    #[getter]
+    fn width(&self) -> u32 {
+        self.field.width()
+    }
+
+    #[getter]
+    fn height(&self) -> u32 {
+        self.field.height()
+    }
+
+    // iteration counts as uint32 or smooth values as float64, shaped (height, width)
+    #[args(smooth = "false", numpy = "false")]
+    fn iterations(&self, py: Python, smooth: bool, numpy: bool) -> PyResult<PyObject> {
+        let (width, height) = (self.field.width(), self.field.height());
+        let frame = if smooth {
+            Frame::values(width, height, self.field.smooth())
+        } else {
+            Frame::counts(width, height, self.field.counts())
+        };
+        frame.into_py_result(py, numpy)
+    }
+
+    // last z of every orbit as float64, shaped (height, width, 2). With the subdivide strategy the
+    // interior pixels of a filled rectangle share the z of its corner
+    #[args(numpy = "false")]
+    fn final_z(&self, py: Python, numpy: bool) -> PyResult<PyObject> {
+        Frame::complex(self.field.width(), self.field.height(), self.field.final_z()).into_py_result(py, numpy)
+    }
+
+    // estimated distance to the set in pixels as float64, shaped (height, width).
+    // NaN for the interior and if the field was rendered without derivatives
+    #[args(numpy = "false")]
+    fn distance(&self, py: Python, numpy: bool) -> PyResult<PyObject> {
+        Frame::values(self.field.width(), self.field.height(), self.field.distances()).into_py_result(py, numpy)
+    }
+
+    // closest approach of the orbit to the trap as float64, shaped (height, width).
+    // NaN if the field was rendered without a trap or the stalks never caught the orbit
+    #[args(numpy = "false")]
+    fn trap(&self, py: Python, numpy: bool) -> PyResult<PyObject> {
+        Frame::values(self.field.width(), self.field.height(), self.field.traps()).into_py_result(py, numpy)
+    }
+
+    // rgb frame shaped (height, width, 3)
+    #[args(smooth = "false", numpy = "false")]
+    fn color(&self, py: Python, smooth: bool, numpy: bool) -> PyResult<PyObject> {
+        let img = self.image(py, smooth);
+        Frame::rgb(img.width(), img.height(), img.into_raw()).into_py_result(py, numpy)
+    }
+
+    // recolors with another palette, same arguments as PlotWindow.set_palette
+    #[args(cycle = "None", offset = "0.0", reverse = "false", interpolation = "None")]
+    fn set_palette(&mut self, name: &str, cycle: Option<f64>, offset: f64, reverse: bool, interpolation: Option<String>) -> PyResult<()> {
+        self.options.palette = palette(name, cycle, offset, reverse, interpolation.as_deref())?;
+        Ok(())
+    }
+
+    // trap needs a field rendered with a trap, stripe and triangle one rendered with the same coloring:
+    // the orbits are gone, what they didn't record can't be colored by
+    fn set_coloring(&mut self, coloring: &str) -> PyResult<()> {
+        let name = coloring;
+        let coloring = coloring_from_name(name)?;
+        let rendered = self.field.options();
+        let recorded = match coloring {
+            Coloring::Trap => rendered.trap.is_some(),
+            Coloring::Stripe | Coloring::Triangle => rendered.coloring == coloring,
+            _ => true,
+        };
+        if !recorded {
+            return Err(PyValueError::new_err(format!("the field wasn't rendered with what the {} coloring needs", name)));
+        }
+        self.options.coloring = coloring;
+        Ok(())
+    }
+
+    // minimum, distance and multiplier only work if the field was rendered with them
+    fn set_interior(&mut self, interior: &str) -> PyResult<()> {
+        self.options.interior = interior_from_name(interior)?;
+        Ok(())
+    }
+
+    // same arguments as PlotWindow.set_lighting
+    #[args(enabled = "true", angle = "45.0", elevation = "45.0", ambient = "0.3", specular = "0.3", height = "1.0")]
+    fn set_lighting(&mut self, enabled: bool, angle: f64, elevation: f64, ambient: f64, specular: f64, height: f64) -> PyResult<()> {
+        self.options.lighting = lighting(enabled, angle, elevation, ambient, specular, height)?;
+        Ok(())
+    }
+
+    #[args(smooth = "false")]
+    fn save(&self, py: Python, out_file: &str, smooth: bool) -> PyResult<String> {
+        self.image(py, smooth).save(out_file).map_err(|e| PyIOError::new_err(e.to_string()))?;
+        Ok(String::from(out_file))
+    }
+}
+
+fn coloring_from_name(name: &str) -> PyResult<Coloring> {
+    Coloring::from_name(name).ok_or_else(|| PyValueError::new_err(format!("unknown coloring: {}", name)))
+}
+
+fn interior_from_name(name: &str) -> PyResult<Interior> {
+    Interior::from_name(name).ok_or_else(|| PyValueError::new_err(format!("unknown interior coloring: {}", name)))
+}
+
+// python int, float or complex
+fn complex(value: &PyAny) -> PyResult<julia::complex::Complex> {
+    match value.downcast::<PyComplex>() {
+        Ok(value) => Ok(julia::complex::Complex::new(value.real(), value.imag())),
+        Err(_) => value.extract::<f64>().map(|value| julia::complex::Complex::new(value, 0.0)),
+    }
+}
+
+fn polynomial_error(e: PolynomialError) -> PyErr {
+    match e {
+        PolynomialError::Degree(degree) => PyValueError::new_err(format!("polynomial needs a degree of at least 2: {}", degree)),
+        PolynomialError::Parameter(index) => PyValueError::new_err(format!("no coefficient {} to take as the parameter", index)),
+    }
+}
+
+fn lighting(enabled: bool, angle: f64, elevation: f64, ambient: f64, specular: f64, height: f64) -> PyResult<Option<Lighting>> {
+    if !enabled {
+        return Ok(None);
+    }
+    if !(0.0..=1.0).contains(&ambient) || !(0.0..=1.0).contains(&specular) {
+        return Err(PyValueError::new_err(format!("ambient and specular have to be in [0, 1]: {}, {}", ambient, specular)));
+    }
+    Ok(Some(Lighting { angle, elevation, ambient, specular, height }))
+}
+
+// cycle None keeps the palette's own, .map files take one iteration per color
+fn palette(name: &str, cycle: Option<f64>, offset: f64, reverse: bool, interpolation: Option<&str>) -> PyResult<Palette> {
+    let mut palette = match Palette::named(name) {
+        Some(palette) => palette,
+        None if name.contains('.') => gradient_file(name)?,
+        None => return Err(PyValueError::new_err(format!("unknown palette: {}", name))),
+    };
+    if let Some(cycle) = cycle {
+        if !(cycle > 0.0 && cycle.is_finite()) {
+            return Err(PyValueError::new_err(format!("palette cycle has to be positive: {}", cycle)));
+        }
+        palette.cycle = cycle;
+    }
+    if !offset.is_finite() {
+        return Err(PyValueError::new_err(format!("palette offset has to be finite: {}", offset)));
+    }
+    if let Some(interpolation) = interpolation {
+        palette.interpolation = Interpolation::from_name(interpolation)
+            .ok_or_else(|| PyValueError::new_err(format!("unknown interpolation: {}", interpolation)))?;
+    }
+    palette.offset = offset;
+    palette.reverse = reverse;
+    Ok(palette)
+}
+
+// path of a .map, .ggr or .ugr file, gradients of a .ugr file past the first get picked by path#title
+fn gradient_file(name: &str) -> PyResult<Palette> {
+    let (path, title) = match name.rsplit_once('#') {
+        Some((path, title)) => (path, Some(title)),
+        None => (name, None),
+    };
+    let gradients = gradients::load(Path::new(path)).map_err(|e| match e {
+        GradientError::Io(message) => PyIOError::new_err(message),
+        e => PyValueError::new_err(format!("can't read gradient {}: {:?}", path, e)),
+    })?;
+    gradients.into_iter()
+        .find(|(name, _)| title.is_none_or(|title| title == name))
+        .map(|(_, palette)| palette)
+        .ok_or_else(|| PyValueError::new_err(format!("no gradient {} in {}", title.unwrap_or(""), path)))
+}
+
+fn parse_error(e: impl Into<julia::view::ParseError>) -> PyErr {
+    match e.into() {
+        julia::view::ParseError::Number(e) => PyValueError::new_err(format!("not a decimal number: {:?}", e)),
+        julia::view::ParseError::Span => PyValueError::new_err("view spans have to be positive"),
+    }
 }
 
 /// A Python module implemented in Rust. The name of this function must match
 /// the `lib.name` setting in the `Cargo.toml`, else Python will not be able to
 /// import the module.
 #[pymodule]
-fn mandelbrot_module(_py: Python, m: &PyModule) -> PyResult<()> {
+fn mandelbrot_module(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(julia, m)?)?;
     m.add_function(wrap_pyfunction!(raw_julia, m)?)?;
     m.add_function(wrap_pyfunction!(mandelbrot, m)?)?;
     m.add_function(wrap_pyfunction!(fine_julia, m)?)?;
     m.add_function(wrap_pyfunction!(fine_mandelbrot, m)?)?;
+    m.add_function(wrap_pyfunction!(mandelbrot_iterations, m)?)?;
+    m.add_function(wrap_pyfunction!(julia_iterations, m)?)?;
+    m.add_function(wrap_pyfunction!(palettes, m)?)?;
+    m.add_function(wrap_pyfunction!(exponent_path, m)?)?;
     m.add_class::<PlotWindow>()?;
+    m.add_class::<RenderHandle>()?;
+    m.add_class::<Frame>()?;
+    m.add_class::<EscapeField>()?;
+    m.add("RenderCancelled", py.get_type::<RenderCancelled>())?;
     Ok(())
 }
 
+// names of the built in palettes
 #[pyfunction]
-fn julia(jx: f64, jy: f64, scale: u32, out_file: &str, tries: u32, power: u32) -> PyResult<String> {
-    julia::single_julia(jx, jy, scale, out_file, tries, power);
-    Ok(String::from(out_file))
+fn palettes() -> Vec<&'static str> {
+    Palette::names().to_vec()
 }
 
+// frames exponents from start to end for set_exponent, the modulus and angle change evenly so
+// the multibrots morph smoothly. Both ends are included
 #[pyfunction]
-fn raw_julia(jx: f64, jy: f64, scale: u32, tries: u32, power: u32) -> PyResult<Vec<u8>> {
-    Ok(julia::raw_single_julia(jx, jy, scale, tries, power))
+fn exponent_path(py: Python, start: &PyAny, end: &PyAny, frames: u32) -> PyResult<Vec<PyObject>> {
+    let (start, end) = (complex(start)?, complex(end)?);
+    let path = julia::polar::exponent_path((start.real(), start.imag()), (end.real(), end.imag()), frames);
+    Ok(path.map(|(x, y)| PyComplex::from_doubles(py, x, y).into()).collect())
 }
 
-#[pyfunction]
-fn mandelbrot(scale: u32, out_file: &str, tries: u32, power: u32) -> PyResult<String> {
-    julia::main_mandelbrot(scale, out_file, tries, power);
-    Ok(String::from(out_file))
+// exponent is a number like PlotWindow.set_exponent takes, None raises z to the power
+fn render_options(py: Python, tries: u32, power: u32, smooth: bool, exponent: Option<PyObject>) -> PyResult<julia::RenderOptions> {
+    let mut options = julia::RenderOptions::new(tries, power);
+    options.smooth = smooth;
+    options.exponent = exponent.map(|exponent| complex(exponent.as_ref(py))).transpose()?;
+    Ok(options)
 }
 
-#[pyfunction]
-fn fine_julia(jx: f64, jy: f64, x_min: f64, x_max: f64, y_min: f64, y_max: f64, scale: u32, out_file: &str, tries: u32, power: u32) -> PyResult<String> {
+// saves the rgb frame of shape (9 * scale, 16 * scale, 3) to out_file and returns it like raw_julia
+#[pyfunction(smooth = "false", numpy = "false", exponent = "None")]
+fn julia(py: Python, jx: f64, jy: f64, scale: u32, out_file: &str, tries: u32, power: u32, smooth: bool, numpy: bool, exponent: Option<PyObject>) -> PyResult<PyObject> {
+    let options = render_options(py, tries, power, smooth, exponent)?;
+    let pixels = py.allow_threads(|| julia::single_julia(jx, jy, scale, out_file, &options));
+    Frame::rgb(16 * scale, 9 * scale, pixels).into_py_result(py, numpy)
+}
+
+// rgb frame of shape (9 * scale, 16 * scale, 3), as numpy array if numpy is true
+#[pyfunction(smooth = "false", numpy = "false", exponent = "None")]
+fn raw_julia(py: Python, jx: f64, jy: f64, scale: u32, tries: u32, power: u32, smooth: bool, numpy: bool, exponent: Option<PyObject>) -> PyResult<PyObject> {
+    let options = render_options(py, tries, power, smooth, exponent)?;
+    let pixels = py.allow_threads(|| julia::raw_single_julia(jx, jy, scale, &options));
+    Frame::rgb(16 * scale, 9 * scale, pixels).into_py_result(py, numpy)
+}
+
+#[pyfunction(smooth = "false", numpy = "false", exponent = "None")]
+fn mandelbrot(py: Python, scale: u32, out_file: &str, tries: u32, power: u32, smooth: bool, numpy: bool, exponent: Option<PyObject>) -> PyResult<PyObject> {
+    let options = render_options(py, tries, power, smooth, exponent)?;
+    let pixels = py.allow_threads(|| julia::main_mandelbrot(scale, out_file, &options));
+    Frame::rgb(16 * scale, 9 * scale, pixels).into_py_result(py, numpy)
+}
+
+#[pyfunction(smooth = "false", numpy = "false", exponent = "None")]
+fn fine_julia(py: Python, jx: f64, jy: f64, x_min: f64, x_max: f64, y_min: f64, y_max: f64, scale: u32, out_file: &str, tries: u32, power: u32, smooth: bool, numpy: bool, exponent: Option<PyObject>) -> PyResult<PyObject> {
+    let options = render_options(py, tries, power, smooth, exponent)?;
     let jul = julia::Julia::new(jx, jy);
-    julia::main_julia(jul, x_min, x_max, y_min, y_max, 16 * scale , 9 * scale, out_file, tries, power);
-    Ok(String::from(out_file))
+    let pixels = py.allow_threads(|| julia::main_julia(jul, x_min, x_max, y_min, y_max, 16 * scale , 9 * scale, out_file, &options));
+    Frame::rgb(16 * scale, 9 * scale, pixels).into_py_result(py, numpy)
 }
 
-#[pyfunction]
-fn fine_mandelbrot(x_min: f64, x_max: f64, y_min: f64, y_max: f64, scale: u32, out_file: &str, tries: u32, power: u32) -> PyResult<String> {
-    julia::fine_mandelbrot(x_min, x_max, y_min, y_max, 16 * scale , 9 * scale, out_file, tries, power);
-    Ok(String::from(out_file))
+#[pyfunction(smooth = "false", numpy = "false", exponent = "None")]
+fn fine_mandelbrot(py: Python, x_min: f64, x_max: f64, y_min: f64, y_max: f64, scale: u32, out_file: &str, tries: u32, power: u32, smooth: bool, numpy: bool, exponent: Option<PyObject>) -> PyResult<PyObject> {
+    let options = render_options(py, tries, power, smooth, exponent)?;
+    let pixels = py.allow_threads(|| julia::fine_mandelbrot(x_min, x_max, y_min, y_max, 16 * scale , 9 * scale, out_file, &options));
+    Frame::rgb(16 * scale, 9 * scale, pixels).into_py_result(py, numpy)
+}
+
+// iteration counts of shape (height, width) as uint32, or the smooth escape values as float64 if smooth is true
+#[pyfunction(smooth = "false", numpy = "false", exponent = "None")]
+fn mandelbrot_iterations(py: Python, x_min: f64, x_max: f64, y_min: f64, y_max: f64, width: u32, height: u32, tries: u32, power: u32, smooth: bool, numpy: bool, exponent: Option<PyObject>) -> PyResult<PyObject> {
+    let options = render_options(py, tries, power, smooth, exponent)?;
+    let field = py.allow_threads(|| julia::mandelbrot_field(x_min, x_max, y_min, y_max, width, height, &options));
+    EscapeField::new(field).iterations(py, smooth, numpy)
+}
+
+#[pyfunction(smooth = "false", numpy = "false", exponent = "None")]
+fn julia_iterations(py: Python, jx: f64, jy: f64, x_min: f64, x_max: f64, y_min: f64, y_max: f64, width: u32, height: u32, tries: u32, power: u32, smooth: bool, numpy: bool, exponent: Option<PyObject>) -> PyResult<PyObject> {
+    let options = render_options(py, tries, power, smooth, exponent)?;
+    let jul = julia::Julia::new(jx, jy);
+    let field = py.allow_threads(|| julia::julia_field(jul, x_min, x_max, y_min, y_max, width, height, &options));
+    EscapeField::new(field).iterations(py, smooth, numpy)
 }
 
 #[cfg(test)]
@@ -201,14 +774,14 @@ mod test{
 
     #[test]
     fn single_julia_test() {
-        julia::single_julia(0.25, 0.0, 60, "./test.png", 50, 2);
+        julia::single_julia(0.25, 0.0, 60, "./test.png", &julia::RenderOptions::new(50, 2));
         let path = Path::new("./test.png");
         remove_file(path).expect("could not delete test.png");
     }
 
     #[test]
     fn raw_julia_test() {
-        let raw = julia::raw_single_julia(0.25, 0.0, 60, 50, 2);
-        assert_eq!(raw.is_empty(), false);
+        let raw = julia::raw_single_julia(0.25, 0.0, 60, &julia::RenderOptions::new(50, 2));
+        assert!(!raw.is_empty());
     }
 }